@@ -0,0 +1,54 @@
+//! Times parsing and printing a large generated expression.
+//! Run with `cargo bench`.
+
+use rox::astprinter::AstPrinter;
+use rox::parser::Parser;
+use rox::scanner::Scanner;
+use std::time::{Duration, Instant};
+
+const TERMS: usize = 5_000;
+const ITERATIONS: u32 = 20;
+
+/// Builds `TERMS` grouped sub-expressions chained together with binary operators.
+fn generate_source() -> String {
+    let operators = ["+", "-", "*", "/", "==", "<", ","];
+    let mut source = String::from("1");
+    for i in 0..TERMS {
+        let operator = operators[i % operators.len()];
+        source.push_str(&format!(
+            " {} (-{} * \"s\" != (true ? nil : {}.5))",
+            operator, i, i
+        ));
+    }
+    source
+}
+
+fn report(name: &str, total: Duration) {
+    println!("{:<6} {:>10.3?} per iteration", name, total / ITERATIONS);
+}
+
+fn main() {
+    let source = generate_source();
+
+    let mut parse_time = Duration::ZERO;
+    let mut print_time = Duration::ZERO;
+    let mut nodes = 0;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let ast = match Parser::from_scanner(Scanner::new(source.clone())).parse() {
+            Ok(ast) => ast,
+            Err(errors) => panic!("generated source failed to parse: {:?}", errors),
+        };
+        parse_time += start.elapsed();
+        nodes = ast.len();
+
+        let start = Instant::now();
+        let printed = AstPrinter::new().print(&ast);
+        print_time += start.elapsed();
+        assert!(!printed.is_empty());
+    }
+
+    println!("{} bytes of source, {} nodes", source.len(), nodes);
+    report("parse", parse_time);
+    report("print", print_time);
+}
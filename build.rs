@@ -0,0 +1,889 @@
+//! Generates the AST node types and their visitors.
+//!
+//! Each node is described by a line of the form `Name ~ field: Type, ...`.
+//! Fields whose type ends in `Id` refer to child nodes stored in the `Ast` arena;
+//! they are handed to visitors by value, everything else is borrowed. A field may
+//! also be a `Vec` or an `Option` of either kind, for nodes with a variable number
+//! of children or an optional part.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const EXPRESSION_TYPES: &[&str] = &[
+    "Assign      ~ name: Token, value: ExprId",
+    "Binary      ~ left: ExprId, operator: Token, right: ExprId",
+    "Call        ~ callee: ExprId, paren: Token, arguments: Vec<ExprId>",
+    "Comma       ~ left: ExprId, right: ExprId",
+    "Conditional ~ condition: ExprId, then_branch: ExprId, else_branch: ExprId",
+    "Get         ~ object: ExprId, name: Token",
+    "Grouping    ~ expression: ExprId",
+    "Index       ~ object: ExprId, bracket: Token, index: ExprId",
+    "Lambda      ~ keyword: Token, params: Vec<Token>, body: Vec<StmtId>",
+    "List        ~ bracket: Token, elements: Vec<ExprId>",
+    "Literal     ~ value: Literal",
+    "Logical     ~ left: ExprId, operator: Token, right: ExprId",
+    // A map's entries alternate between keys and values, so they stay in source order.
+    "Map         ~ brace: Token, entries: Vec<ExprId>",
+    "SetIndex    ~ object: ExprId, bracket: Token, index: ExprId, value: ExprId",
+    "Slice       ~ object: ExprId, bracket: Token, start: Option<ExprId>, end: Option<ExprId>",
+    "Unary       ~ operator: Token, right: ExprId",
+    "Variable    ~ name: Token",
+];
+
+const STATEMENT_TYPES: &[&str] = &[
+    "Block      ~ statements: Vec<StmtId>",
+    "Break      ~ keyword: Token",
+    "Continue   ~ keyword: Token",
+    "Expression ~ expression: ExprId",
+    "ForIn      ~ name: Token, iterable: ExprId, body: StmtId",
+    "Function   ~ name: Token, params: Vec<Token>, body: Vec<StmtId>",
+    "If         ~ condition: ExprId, then_branch: StmtId, else_branch: Option<StmtId>",
+    "Print      ~ expression: ExprId",
+    "Return     ~ keyword: Token, value: Option<ExprId>",
+    "Throw      ~ keyword: Token, value: ExprId",
+    "Try        ~ body: StmtId, name: Option<Token>, handler: Option<StmtId>, finalizer: Option<StmtId>",
+    "Var        ~ name: Token, initializer: Option<ExprId>",
+    "While      ~ condition: ExprId, body: StmtId, increment: Option<ExprId>",
+];
+
+/// How many values of its type a field holds.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Shape {
+    One,
+    List,
+    Optional,
+}
+
+struct Field {
+    name: String,
+    field_type: String,
+    shape: Shape,
+    /// The type of each value, without the `Vec` or `Option` around it.
+    inner: String,
+}
+
+impl Field {
+    fn new(name: &str, field_type: &str) -> Self {
+        let wrapped = |wrapper: &str| {
+            field_type
+                .strip_prefix(wrapper)
+                .and_then(|rest| rest.strip_suffix('>'))
+                .map(|inner| inner.trim().to_owned())
+        };
+        let (shape, inner) = if let Some(inner) = wrapped("Vec<") {
+            (Shape::List, inner)
+        } else if let Some(inner) = wrapped("Option<") {
+            (Shape::Optional, inner)
+        } else {
+            (Shape::One, field_type.to_owned())
+        };
+        Field {
+            name: name.to_owned(),
+            field_type: field_type.to_owned(),
+            shape,
+            inner,
+        }
+    }
+
+    fn is_id(&self) -> bool {
+        self.inner.ends_with("Id")
+    }
+
+    /// The type a visitor receives this field as.
+    fn param_type(&self) -> String {
+        match (self.shape, self.is_id()) {
+            (Shape::One, true) => self.inner.clone(),
+            (Shape::One, false) => format!("&{}", self.inner),
+            (Shape::List, _) => format!("&[{}]", self.inner),
+            (Shape::Optional, true) => format!("Option<{}>", self.inner),
+            (Shape::Optional, false) => format!("Option<&{}>", self.inner),
+        }
+    }
+
+    /// The expression passing this field, bound by reference in a match, to a visitor.
+    fn arg(&self) -> String {
+        match (self.shape, self.is_id()) {
+            (Shape::One, true) | (Shape::Optional, true) => format!("*{}", self.name),
+            (Shape::Optional, false) => format!("{}.as_ref()", self.name),
+            _ => self.name.clone(),
+        }
+    }
+
+    /// The expression formatting this field, bound by reference, for `Display`.
+    fn display(&self) -> String {
+        match self.shape {
+            Shape::One => self.name.clone(),
+            Shape::List => format!("display_list({})", self.name),
+            Shape::Optional => format!("display_option({}.as_ref())", self.name),
+        }
+    }
+
+    /// The IDs this field holds, as an expression of type `Vec<Id>`, given the field
+    /// bound by reference.
+    fn ids(&self) -> String {
+        match self.shape {
+            Shape::One => format!("vec![*{}]", self.name),
+            Shape::List => format!("{}.clone()", self.name),
+            Shape::Optional => format!("{}.iter().copied().collect::<Vec<_>>()", self.name),
+        }
+    }
+
+    /// The expression copying this field, bound by reference, out of the node.
+    fn copied(&self) -> String {
+        match self.shape {
+            Shape::One | Shape::Optional => format!("*{}", self.name),
+            Shape::List => format!("{}.clone()", self.name),
+        }
+    }
+}
+
+struct NodeType {
+    name: String,
+    fields: Vec<Field>,
+}
+
+impl NodeType {
+    fn parse(definition: &str) -> Self {
+        let (name, fields) = definition
+            .split_once('~')
+            .expect("node definitions look like `Name ~ field: Type, ...`");
+        let fields = fields
+            .split(',')
+            .map(|field| {
+                let (name, field_type) = field
+                    .split_once(':')
+                    .expect("fields look like `name: Type`");
+                Field::new(name.trim(), field_type.trim())
+            })
+            .collect();
+        NodeType {
+            name: name.trim().to_owned(),
+            fields,
+        }
+    }
+
+    fn field_names(&self) -> String {
+        self.join(|field| field.name.clone())
+    }
+
+    fn args(&self) -> String {
+        self.join(Field::arg)
+    }
+
+    fn params(&self) -> String {
+        self.join(|field| format!("{}: {}", field.name, field.param_type()))
+    }
+
+    fn join(&self, f: impl Fn(&Field) -> String) -> String {
+        self.fields.iter().map(f).collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// `snake_case` turns a node name such as `SetIndex` into `set_index`, for method names.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
+
+/// Where the nodes of a base sit at the top of a program.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum TopLevel {
+    /// The root of a program that is a bare expression.
+    Root,
+    /// The statements of a program.
+    Statements,
+}
+
+/// The description of one node enum to generate.
+struct Base {
+    name: &'static str,
+    /// The name of the `Visitor` method that dispatches on a node.
+    entry: &'static str,
+    top_level: TopLevel,
+    types: Vec<NodeType>,
+}
+
+impl Base {
+    fn lower(&self) -> String {
+        self.name.to_lowercase()
+    }
+
+    fn id(&self) -> String {
+        format!("{}Id", self.name)
+    }
+
+    fn visit_method(&self, node: &NodeType) -> String {
+        format!("visit_{}_{}", snake_case(&node.name), self.lower())
+    }
+
+    fn fold_method(&self, node: &NodeType) -> String {
+        format!("fold_{}_{}", snake_case(&node.name), self.lower())
+    }
+
+    /// The base that `field` refers to, which need not be this one.
+    fn child_base(&self, field: &Field) -> String {
+        field.inner.trim_end_matches("Id").to_lowercase()
+    }
+
+    fn has_lists(&self) -> bool {
+        self.types
+            .iter()
+            .flat_map(|node| &node.fields)
+            .any(|field| field.shape == Shape::List)
+    }
+
+    fn has_options(&self) -> bool {
+        self.types
+            .iter()
+            .flat_map(|node| &node.fields)
+            .any(|field| field.shape == Shape::Optional)
+    }
+}
+
+fn define_enum(out: &mut String, base: &Base) {
+    writeln!(out, "#[derive(Debug, Clone, PartialEq)]").unwrap();
+    writeln!(out, "pub enum {} {{", base.name).unwrap();
+    for node in &base.types {
+        let fields = node.join(|field| field.field_type.clone());
+        writeln!(out, "    {}({}),", node.name, fields).unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+}
+
+fn define_children(out: &mut String, base: &Base) {
+    writeln!(out, "impl {} {{", base.name).unwrap();
+    writeln!(
+        out,
+        "    /// The IDs of this node's direct `{}` children, in source order.",
+        base.name
+    )
+    .unwrap();
+    writeln!(out, "    pub fn children(&self) -> Vec<{}> {{", base.id()).unwrap();
+    writeln!(out, "        match self {{").unwrap();
+    for node in &base.types {
+        let own = |field: &Field| field.inner == base.id();
+        let patterns = node.join(|field| {
+            if own(field) {
+                field.name.clone()
+            } else {
+                String::from("_")
+            }
+        });
+        let fields: Vec<&Field> = node.fields.iter().filter(|field| own(field)).collect();
+        let children = if fields.iter().all(|field| field.shape == Shape::One) {
+            let ids = fields.iter().map(|field| field.arg()).collect::<Vec<_>>();
+            format!("vec![{}]", ids.join(", "))
+        } else if fields.len() == 1 {
+            fields[0].ids()
+        } else {
+            let parts = fields.iter().map(|field| field.ids()).collect::<Vec<_>>();
+            format!("[{}].concat()", parts.join(", "))
+        };
+        writeln!(
+            out,
+            "            {}::{}({}) => {},",
+            base.name, node.name, patterns, children
+        )
+        .unwrap();
+    }
+    writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+}
+
+fn define_display(out: &mut String, base: &Base) {
+    writeln!(out, "impl fmt::Display for {} {{", base.name).unwrap();
+    writeln!(
+        out,
+        "    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {{"
+    )
+    .unwrap();
+    writeln!(out, "        match self {{").unwrap();
+    for node in &base.types {
+        let placeholders = node.join(|_| String::from("{}"));
+        writeln!(
+            out,
+            "            {}::{}({}) => write!(f, \"{}({})\", {}),",
+            base.name,
+            node.name,
+            node.field_names(),
+            node.name,
+            placeholders,
+            node.join(Field::display)
+        )
+        .unwrap();
+    }
+    writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+
+    if base.has_lists() {
+        writeln!(
+            out,
+            "fn display_list<T: fmt::Display>(items: &[T]) -> String {{"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();"
+        )
+        .unwrap();
+        writeln!(out, "    format!(\"[{{}}]\", items.join(\", \"))\n}}\n").unwrap();
+    }
+    if base.has_options() {
+        writeln!(
+            out,
+            "fn display_option<T: fmt::Display>(item: Option<&T>) -> String {{"
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "    item.map_or(String::from(\"-\"), |item| item.to_string())\n}}\n"
+        )
+        .unwrap();
+    }
+}
+
+fn define_visitor(out: &mut String, base: &Base) {
+    writeln!(
+        out,
+        "/// A `{}Visitor` computes a value from each node of an `Ast`, which it borrows.",
+        base.name
+    )
+    .unwrap();
+    writeln!(out, "pub trait {}Visitor<T> {{", base.name).unwrap();
+    writeln!(
+        out,
+        "    fn {}(&mut self, ast: &Ast, id: {}) -> T {{",
+        base.entry,
+        base.id()
+    )
+    .unwrap();
+    writeln!(out, "        match ast.get(id) {{").unwrap();
+    for node in &base.types {
+        writeln!(
+            out,
+            "            {}::{}({}) => self.{}(ast, id, {}),",
+            base.name,
+            node.name,
+            node.field_names(),
+            base.visit_method(node),
+            node.args()
+        )
+        .unwrap();
+    }
+    writeln!(out, "        }}\n    }}").unwrap();
+    for node in &base.types {
+        writeln!(
+            out,
+            "    fn {}(&mut self, ast: &Ast, id: {}, {}) -> T;",
+            base.visit_method(node),
+            base.id(),
+            node.params()
+        )
+        .unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+}
+
+/// `define_visitor_mut` writes a `VisitorMut` covering every base, since nodes of
+/// each base may have children of the others.
+fn define_visitor_mut(out: &mut String, bases: &[Base]) {
+    writeln!(
+        out,
+        "/// A `VisitorMut` walks an `Ast` and may rewrite nodes in place."
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "/// Every method defaults to visiting the node's children, so a pass only overrides"
+    )
+    .unwrap();
+    writeln!(out, "/// the nodes it cares about.").unwrap();
+    writeln!(out, "pub trait VisitorMut {{").unwrap();
+    writeln!(
+        out,
+        "    /// `visit_ast_mut` visits the root of `ast`, if it has one, and each of its statements."
+    )
+    .unwrap();
+    writeln!(out, "    fn visit_ast_mut(&mut self, ast: &mut Ast) {{").unwrap();
+    for base in bases {
+        let lower = base.lower();
+        match base.top_level {
+            TopLevel::Root => {
+                writeln!(out, "        if let Some(root) = ast.root() {{").unwrap();
+                writeln!(out, "            self.visit_{}_mut(ast, root);", lower).unwrap();
+                writeln!(out, "        }}").unwrap();
+            }
+            TopLevel::Statements => {
+                writeln!(out, "        for statement in ast.statements().to_vec() {{").unwrap();
+                writeln!(out, "            self.visit_{}_mut(ast, statement);", lower).unwrap();
+                writeln!(out, "        }}").unwrap();
+            }
+        }
+    }
+    writeln!(out, "    }}").unwrap();
+    for base in bases {
+        let lower = base.lower();
+        writeln!(
+            out,
+            "    fn visit_{}_mut(&mut self, ast: &mut Ast, id: {}) {{",
+            lower,
+            base.id()
+        )
+        .unwrap();
+        writeln!(out, "        match ast.get(id) {{").unwrap();
+        for node in &base.types {
+            writeln!(
+                out,
+                "            {}::{}(..) => self.{}_mut(ast, id),",
+                base.name,
+                node.name,
+                base.visit_method(node)
+            )
+            .unwrap();
+        }
+        writeln!(out, "        }}\n    }}").unwrap();
+        for node in &base.types {
+            writeln!(
+                out,
+                "    fn {}_mut(&mut self, ast: &mut Ast, id: {}) {{",
+                base.visit_method(node),
+                base.id()
+            )
+            .unwrap();
+            writeln!(out, "        walk_{}_mut(self, ast, id);", lower).unwrap();
+            writeln!(out, "    }}").unwrap();
+        }
+    }
+    writeln!(out, "}}\n").unwrap();
+}
+
+/// `define_fold` writes a `Fold` covering every base, for the same reason as
+/// `define_visitor_mut`.
+fn define_fold(out: &mut String, bases: &[Base]) {
+    writeln!(
+        out,
+        "/// A `Fold` builds a new `Ast` out of an existing one."
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "/// Every method defaults to folding the node's children and rebuilding the node"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "/// with the same span, so a pass only overrides the nodes it rewrites."
+    )
+    .unwrap();
+    writeln!(out, "pub trait Fold {{").unwrap();
+    writeln!(
+        out,
+        "    /// `fold` folds the root of `ast`, if it has one, and each of its statements into a new `Ast`."
+    )
+    .unwrap();
+    writeln!(out, "    fn fold(&mut self, ast: &Ast) -> Ast {{").unwrap();
+    writeln!(out, "        let mut out = Ast::new();").unwrap();
+    for base in bases {
+        let lower = base.lower();
+        match base.top_level {
+            TopLevel::Root => {
+                writeln!(out, "        if let Some(root) = ast.root() {{").unwrap();
+                writeln!(
+                    out,
+                    "            let root = self.fold_{}(ast, root, &mut out);",
+                    lower
+                )
+                .unwrap();
+                writeln!(out, "            out.set_root(root);").unwrap();
+                writeln!(out, "        }}").unwrap();
+            }
+            TopLevel::Statements => {
+                writeln!(out, "        for &statement in ast.statements() {{").unwrap();
+                writeln!(
+                    out,
+                    "            let statement = self.fold_{}(ast, statement, &mut out);",
+                    lower
+                )
+                .unwrap();
+                writeln!(out, "            out.add_statement(statement);").unwrap();
+                writeln!(out, "        }}").unwrap();
+            }
+        }
+    }
+    writeln!(out, "        out\n    }}").unwrap();
+    for base in bases {
+        define_fold_methods(out, base);
+    }
+    writeln!(out, "}}\n").unwrap();
+}
+
+fn define_fold_methods(out: &mut String, base: &Base) {
+    writeln!(
+        out,
+        "    fn fold_{}(&mut self, ast: &Ast, id: {}, out: &mut Ast) -> {} {{",
+        base.lower(),
+        base.id(),
+        base.id()
+    )
+    .unwrap();
+    writeln!(out, "        match ast.get(id) {{").unwrap();
+    for node in &base.types {
+        writeln!(
+            out,
+            "            {}::{}({}) => self.{}(ast, id, {}, out),",
+            base.name,
+            node.name,
+            node.field_names(),
+            base.fold_method(node),
+            node.args()
+        )
+        .unwrap();
+    }
+    writeln!(out, "        }}\n    }}").unwrap();
+    for node in &base.types {
+        // `self`, `ast`, `id` and `out` come on top of the node's fields.
+        if node.fields.len() + 4 > 7 {
+            writeln!(out, "    #[allow(clippy::too_many_arguments)]").unwrap();
+        }
+        writeln!(
+            out,
+            "    fn {}(&mut self, ast: &Ast, id: {}, {}, out: &mut Ast) -> {} {{",
+            base.fold_method(node),
+            base.id(),
+            node.params(),
+            base.id()
+        )
+        .unwrap();
+        for field in node.fields.iter().filter(|field| field.is_id()) {
+            let fold = format!("self.fold_{}(ast, child, out)", base.child_base(field));
+            let folded = match field.shape {
+                Shape::One => format!(
+                    "self.fold_{}(ast, {}, out)",
+                    base.child_base(field),
+                    field.name
+                ),
+                Shape::List => format!(
+                    "{}.iter().map(|&child| {}).collect::<Vec<_>>()",
+                    field.name, fold
+                ),
+                Shape::Optional => format!("{}.map(|child| {})", field.name, fold),
+            };
+            writeln!(out, "        let {} = {};", field.name, folded).unwrap();
+        }
+        let rebuilt = node.join(|field| match (field.shape, field.is_id()) {
+            (_, true) => field.name.clone(),
+            (Shape::One, false) => format!("{}.clone()", field.name),
+            (Shape::List, false) => format!("{}.to_vec()", field.name),
+            (Shape::Optional, false) => format!("{}.cloned()", field.name),
+        });
+        writeln!(
+            out,
+            "        out.add({}::{}({}), ast.span(id))",
+            base.name, node.name, rebuilt
+        )
+        .unwrap();
+        writeln!(out, "    }}").unwrap();
+    }
+}
+
+fn define_walk(out: &mut String, base: &Base) {
+    let lower = base.lower();
+    writeln!(
+        out,
+        "/// `walk_{}` runs `visitor` over each direct `{}` child of `id`, in source order.",
+        lower, base.name
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub fn walk_{}<T, V: {}Visitor<T> + ?Sized>(visitor: &mut V, ast: &Ast, id: {}) -> Vec<T> {{",
+        lower,
+        base.name,
+        base.id()
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    ast.get(id).children().into_iter().map(|child| visitor.{}(ast, child)).collect()",
+        base.entry
+    )
+    .unwrap();
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(
+        out,
+        "/// `walk_{}_mut` visits each direct child of `id` with `visitor`, in source order.",
+        lower
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub fn walk_{}_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, id: {}) {{",
+        lower,
+        base.id()
+    )
+    .unwrap();
+    writeln!(out, "    match ast.get(id) {{").unwrap();
+    for node in &base.types {
+        let ids: Vec<&Field> = node.fields.iter().filter(|field| field.is_id()).collect();
+        if ids.is_empty() {
+            writeln!(out, "        {}::{}(..) => {{}}", base.name, node.name).unwrap();
+            continue;
+        }
+        let patterns = node.join(|field| {
+            if field.is_id() {
+                field.name.clone()
+            } else {
+                String::from("_")
+            }
+        });
+        writeln!(
+            out,
+            "        {}::{}({}) => {{",
+            base.name, node.name, patterns
+        )
+        .unwrap();
+        // Copy the IDs out first, so `ast` is free to be borrowed mutably.
+        for field in &ids {
+            writeln!(out, "            let {} = {};", field.name, field.copied()).unwrap();
+        }
+        for field in ids {
+            let visit = format!("visitor.visit_{}_mut(ast, child);", base.child_base(field));
+            match field.shape {
+                Shape::One => writeln!(
+                    out,
+                    "            visitor.visit_{}_mut(ast, {});",
+                    base.child_base(field),
+                    field.name
+                ),
+                Shape::List => writeln!(
+                    out,
+                    "            for child in {} {{\n                {}\n            }}",
+                    field.name, visit
+                ),
+                Shape::Optional => writeln!(
+                    out,
+                    "            if let Some(child) = {} {{\n                {}\n            }}",
+                    field.name, visit
+                ),
+            }
+            .unwrap();
+        }
+        writeln!(out, "        }}").unwrap();
+    }
+    writeln!(out, "    }}\n}}\n").unwrap();
+}
+
+/// The type a field has in the nested `Tree` form of a node.
+fn tree_type(field: &Field) -> String {
+    if !field.is_id() {
+        return field.field_type.clone();
+    }
+    let tree = format!("{}Tree", field.inner.trim_end_matches("Id"));
+    match field.shape {
+        Shape::One => format!("Box<{}>", tree),
+        Shape::List => format!("Vec<{}>", tree),
+        Shape::Optional => format!("Option<Box<{}>>", tree),
+    }
+}
+
+fn define_tree(out: &mut String, base: &Base) {
+    let tree = format!("{}Tree", base.name);
+    writeln!(
+        out,
+        "/// An `{}` is the owned, nested form of an `{}` and everything under it.",
+        tree, base.name
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "/// It is what the JSON encoding of an AST is read into and written from."
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]"
+    )
+    .unwrap();
+    writeln!(out, "#[serde(tag = \"type\")]").unwrap();
+    writeln!(out, "pub enum {} {{", tree).unwrap();
+    for node in &base.types {
+        writeln!(out, "    {} {{", node.name).unwrap();
+        writeln!(out, "        span: Span,").unwrap();
+        for field in &node.fields {
+            writeln!(out, "        {}: {},", field.name, tree_type(field)).unwrap();
+        }
+        writeln!(out, "    }},").unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(out, "impl {} {{", tree).unwrap();
+    writeln!(
+        out,
+        "    /// `from_ast` copies the node `id` and its descendants out of `ast`."
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    pub fn from_ast(ast: &Ast, id: {}) -> Self {{",
+        base.id()
+    )
+    .unwrap();
+    writeln!(out, "        match ast.get(id) {{").unwrap();
+    for node in &base.types {
+        writeln!(
+            out,
+            "            {}::{}({}) => {}::{} {{",
+            base.name,
+            node.name,
+            node.field_names(),
+            tree,
+            node.name
+        )
+        .unwrap();
+        writeln!(out, "                span: ast.span(id),").unwrap();
+        for field in &node.fields {
+            if field.is_id() {
+                let tree = format!("{}Tree", field.inner.trim_end_matches("Id"));
+                let copied = match field.shape {
+                    Shape::One => format!("Box::new({}::from_ast(ast, *{}))", tree, field.name),
+                    Shape::List => format!(
+                        "{}.iter().map(|&child| {}::from_ast(ast, child)).collect()",
+                        field.name, tree
+                    ),
+                    Shape::Optional => format!(
+                        "{}.map(|child| Box::new({}::from_ast(ast, child)))",
+                        field.name, tree
+                    ),
+                };
+                writeln!(out, "                {}: {},", field.name, copied).unwrap();
+            } else {
+                writeln!(
+                    out,
+                    "                {}: {}.clone(),",
+                    field.name, field.name
+                )
+                .unwrap();
+            }
+        }
+        writeln!(out, "            }},").unwrap();
+    }
+    writeln!(out, "        }}\n    }}\n").unwrap();
+    writeln!(
+        out,
+        "    /// `into_ast` stores this node and its descendants in `ast`, returning the node's ID."
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    pub fn into_ast(self, ast: &mut Ast) -> {} {{",
+        base.id()
+    )
+    .unwrap();
+    writeln!(out, "        match self {{").unwrap();
+    for node in &base.types {
+        writeln!(
+            out,
+            "            {}::{} {{ span, {} }} => {{",
+            tree,
+            node.name,
+            node.field_names()
+        )
+        .unwrap();
+        for field in node.fields.iter().filter(|field| field.is_id()) {
+            let stored = match field.shape {
+                Shape::One => format!("{}.into_ast(ast)", field.name),
+                Shape::List => format!(
+                    "{}.into_iter().map(|child| child.into_ast(ast)).collect()",
+                    field.name
+                ),
+                Shape::Optional => format!("{}.map(|child| child.into_ast(ast))", field.name),
+            };
+            writeln!(out, "                let {} = {};", field.name, stored).unwrap();
+        }
+        writeln!(
+            out,
+            "                ast.add({}::{}({}), span)",
+            base.name,
+            node.name,
+            node.field_names()
+        )
+        .unwrap();
+        writeln!(out, "            }}").unwrap();
+    }
+    writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+}
+
+fn define_ast(base: &Base, bases: &[Base]) -> String {
+    let mut out = String::new();
+    writeln!(out, "// generated by build.rs\n").unwrap();
+    writeln!(out, "use crate::ast::*;").unwrap();
+    for other in bases.iter().filter(|other| other.name != base.name) {
+        writeln!(out, "#[allow(unused_imports)]").unwrap();
+        writeln!(out, "use crate::{}::*;", other.lower()).unwrap();
+    }
+    writeln!(out, "#[allow(unused_imports)]").unwrap();
+    writeln!(out, "use crate::token::{{Literal, Span, Token}};").unwrap();
+    writeln!(out, "use crate::visit::VisitorMut;").unwrap();
+    writeln!(out, "use serde::{{Deserialize, Serialize}};").unwrap();
+    writeln!(out, "use std::fmt;\n").unwrap();
+    define_enum(&mut out, base);
+    define_children(&mut out, base);
+    define_display(&mut out, base);
+    define_visitor(&mut out, base);
+    define_walk(&mut out, base);
+    define_tree(&mut out, base);
+    out
+}
+
+/// `define_passes` writes the passes that cross from one base to another.
+fn define_passes(bases: &[Base]) -> String {
+    let mut out = String::new();
+    writeln!(out, "// generated by build.rs\n").unwrap();
+    writeln!(out, "use crate::ast::*;").unwrap();
+    for base in bases {
+        writeln!(out, "use crate::{}::*;", base.lower()).unwrap();
+    }
+    writeln!(out, "#[allow(unused_imports)]").unwrap();
+    writeln!(out, "use crate::token::{{Literal, Token}};\n").unwrap();
+    define_visitor_mut(&mut out, bases);
+    define_fold(&mut out, bases);
+    out
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    let out_dir = env::var("OUT_DIR").expect("cargo sets OUT_DIR for build scripts");
+
+    let bases = [
+        Base {
+            name: "Expr",
+            entry: "evaluate",
+            top_level: TopLevel::Root,
+            types: EXPRESSION_TYPES
+                .iter()
+                .map(|t| NodeType::parse(t))
+                .collect(),
+        },
+        Base {
+            name: "Stmt",
+            entry: "execute",
+            top_level: TopLevel::Statements,
+            types: STATEMENT_TYPES.iter().map(|t| NodeType::parse(t)).collect(),
+        },
+    ];
+    let write = |name: &str, contents: String| {
+        let path = Path::new(&out_dir).join(format!("{}.rs", name));
+        fs::write(&path, contents).expect("failed to write generated AST");
+    };
+    for base in &bases {
+        write(&base.lower(), define_ast(base, &bases));
+    }
+    write("visit", define_passes(&bases));
+}
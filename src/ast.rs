@@ -0,0 +1,149 @@
+use crate::expr::Expr;
+use crate::stmt::Stmt;
+use crate::token::Span;
+use std::fmt;
+
+/// An `ExprId` identifies an `Expr` stored in an `Ast`.
+/// IDs are only meaningful for the `Ast` that handed them out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExprId(usize);
+
+/// A `StmtId` identifies a `Stmt` stored in an `Ast`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StmtId(usize);
+
+/// `NodeId` is implemented by the ID types of the arena, and lets `Ast` find
+/// the table that holds the kind of node an ID refers to.
+pub trait NodeId: Copy {
+    type Node;
+
+    /// The position of the node in its table, for use as an index into side tables.
+    fn index(&self) -> usize;
+    fn from_index(index: usize) -> Self;
+    fn table(ast: &Ast) -> &Table<Self::Node>;
+    fn table_mut(ast: &mut Ast) -> &mut Table<Self::Node>;
+}
+
+/// `Node` is implemented by the node types stored in the arena.
+pub trait Node: Sized {
+    type Id: NodeId<Node = Self>;
+}
+
+/// A `Table` holds every node of one kind along with its span.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table<T> {
+    nodes: Vec<T>,
+    spans: Vec<Span>,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Table {
+            nodes: vec![],
+            spans: vec![],
+        }
+    }
+}
+
+macro_rules! node_id {
+    ($id:ident, $node:ident, $table:ident) => {
+        impl NodeId for $id {
+            type Node = $node;
+
+            fn index(&self) -> usize {
+                self.0
+            }
+
+            fn from_index(index: usize) -> Self {
+                $id(index)
+            }
+
+            fn table(ast: &Ast) -> &Table<$node> {
+                &ast.$table
+            }
+
+            fn table_mut(ast: &mut Ast) -> &mut Table<$node> {
+                &mut ast.$table
+            }
+        }
+
+        impl Node for $node {
+            type Id = $id;
+        }
+
+        impl fmt::Display for $id {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "#{}", self.0)
+            }
+        }
+    };
+}
+
+node_id!(ExprId, Expr, exprs);
+node_id!(StmtId, Stmt, stmts);
+
+/// An `Ast` is an arena holding every node of a parsed program.
+/// Nodes refer to their children by ID, and per-node data such as spans
+/// is kept in tables indexed by the same ID.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Ast {
+    exprs: Table<Expr>,
+    stmts: Table<Stmt>,
+    root: Option<ExprId>,
+    statements: Vec<StmtId>,
+}
+
+impl Ast {
+    pub fn new() -> Self {
+        Ast::default()
+    }
+
+    /// `add` stores `node` in the arena and returns its ID.
+    pub fn add<N: Node>(&mut self, node: N, span: Span) -> N::Id {
+        let table = N::Id::table_mut(self);
+        table.nodes.push(node);
+        table.spans.push(span);
+        N::Id::from_index(table.nodes.len() - 1)
+    }
+
+    pub fn get<I: NodeId>(&self, id: I) -> &I::Node {
+        &I::table(self).nodes[id.index()]
+    }
+
+    /// `get_mut` gives access to a node for rewriting it in place.
+    pub fn get_mut<I: NodeId>(&mut self, id: I) -> &mut I::Node {
+        &mut I::table_mut(self).nodes[id.index()]
+    }
+
+    pub fn span<I: NodeId>(&self, id: I) -> Span {
+        I::table(self).spans[id.index()]
+    }
+
+    /// The outermost expression, when the source was a bare expression rather
+    /// than a program of statements.
+    pub fn root(&self) -> Option<ExprId> {
+        self.root
+    }
+
+    pub fn set_root(&mut self, id: ExprId) {
+        self.root = Some(id);
+    }
+
+    /// The top-level statements of the program, in source order.
+    pub fn statements(&self) -> &[StmtId] {
+        &self.statements
+    }
+
+    pub fn add_statement(&mut self, id: StmtId) {
+        self.statements.push(id);
+    }
+
+    /// The number of nodes in the arena.
+    pub fn len(&self) -> usize {
+        self.exprs.nodes.len() + self.stmts.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
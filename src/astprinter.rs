@@ -1,21 +1,49 @@
+#[derive(Default)]
 pub struct AstPrinter {}
 
-use crate::expr::{Expr, ExprVisitor};
+use crate::ast::{Ast, ExprId, StmtId};
+use crate::expr::{walk_expr, ExprVisitor};
+use crate::stmt::StmtVisitor;
 use crate::token::{Literal, Token};
 
 impl AstPrinter {
     pub fn new() -> Self {
         AstPrinter {}
     }
-    pub fn print(&mut self, expr: Expr) -> String {
-        self.evaluate(expr)
+
+    /// `print` renders the root expression of `ast`, or else its statements, one
+    /// per line.
+    pub fn print(&mut self, ast: &Ast) -> String {
+        match ast.root() {
+            Some(root) => self.evaluate(ast, root),
+            None => self.statements(ast, ast.statements()).join("\n"),
+        }
     }
 
-    fn parenthesize(&mut self, name: String, exprs: &[Expr]) -> String {
+    fn statements(&mut self, ast: &Ast, statements: &[StmtId]) -> Vec<String> {
+        statements
+            .iter()
+            .map(|&statement| self.execute(ast, statement))
+            .collect()
+    }
+
+    /// `function` prints the parameters and body of a function after `name`.
+    fn function(&mut self, ast: &Ast, name: String, params: &[Token], body: &[StmtId]) -> String {
+        let params: Vec<String> = params.iter().map(Token::lexeme).collect();
+        let mut function = format!("({} ({})", name, params.join(" "));
+        for statement in self.statements(ast, body) {
+            function.push(' ');
+            function.push_str(&statement);
+        }
+        function.push(')');
+        function
+    }
+
+    /// `parenthesize` prints the children of `id` after `name`, wrapped in parentheses.
+    fn parenthesize(&mut self, ast: &Ast, id: ExprId, name: String) -> String {
         let mut expr_str = format!("({}", name);
-        for expr in exprs {
+        for result in walk_expr(self, ast, id) {
             expr_str.push(' ');
-            let result = self.evaluate(expr.clone());
             expr_str.push_str(result.as_str());
         }
         expr_str.push(')');
@@ -24,31 +52,314 @@ impl AstPrinter {
 }
 
 impl ExprVisitor<String> for AstPrinter {
-    fn visit_binary_expr(&mut self, left: Box<Expr>, op: Token, right: Box<Expr>) -> String {
-        let left = *left;
-        let right = *right;
-        self.parenthesize(op.lexeme(), &[left, right])
+    fn visit_assign_expr(&mut self, ast: &Ast, id: ExprId, name: &Token, _value: ExprId) -> String {
+        self.parenthesize(ast, id, format!("= {}", name.lexeme()))
     }
 
-    fn visit_grouping_expr(&mut self, expr: Box<Expr>) -> String {
-        let expr = *expr;
-        self.parenthesize(String::from("group"), &[expr])
+    fn visit_binary_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _left: ExprId,
+        op: &Token,
+        _right: ExprId,
+    ) -> String {
+        self.parenthesize(ast, id, op.lexeme())
     }
 
-    fn visit_literal_expr(&mut self, value: Literal) -> String {
-        if let Literal::Nil = value {
-            return String::from("nil");
-        }
+    fn visit_call_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _callee: ExprId,
+        _paren: &Token,
+        _arguments: &[ExprId],
+    ) -> String {
+        self.parenthesize(ast, id, String::from("call"))
+    }
+
+    fn visit_comma_expr(&mut self, ast: &Ast, id: ExprId, _left: ExprId, _right: ExprId) -> String {
+        self.parenthesize(ast, id, String::from(","))
+    }
+
+    fn visit_conditional_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _condition: ExprId,
+        _then_branch: ExprId,
+        _else_branch: ExprId,
+    ) -> String {
+        self.parenthesize(ast, id, String::from("?:"))
+    }
+
+    fn visit_get_expr(&mut self, ast: &Ast, _id: ExprId, object: ExprId, name: &Token) -> String {
+        format!("(. {} {})", self.evaluate(ast, object), name.lexeme())
+    }
+
+    fn visit_grouping_expr(&mut self, ast: &Ast, id: ExprId, _expr: ExprId) -> String {
+        self.parenthesize(ast, id, String::from("group"))
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _object: ExprId,
+        _bracket: &Token,
+        _index: ExprId,
+    ) -> String {
+        self.parenthesize(ast, id, String::from("[]"))
+    }
+
+    fn visit_lambda_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        _keyword: &Token,
+        params: &[Token],
+        body: &[StmtId],
+    ) -> String {
+        self.function(ast, String::from("fun"), params, body)
+    }
+
+    fn visit_list_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _bracket: &Token,
+        _elements: &[ExprId],
+    ) -> String {
+        self.parenthesize(ast, id, String::from("list"))
+    }
+
+    fn visit_map_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _brace: &Token,
+        _entries: &[ExprId],
+    ) -> String {
+        self.parenthesize(ast, id, String::from("map"))
+    }
+
+    fn visit_literal_expr(&mut self, _ast: &Ast, _id: ExprId, value: &Literal) -> String {
         match value {
             Literal::Nil => String::from("nil"),
             Literal::Number(value) => format!("{}", value),
             Literal::Boolean(value) => format!("{}", value),
-            Literal::LoxString(value) => value,
+            // Quoted, so that a string can't be taken for a variable.
+            Literal::LoxString(value) => format!("\"{}\"", value),
         }
     }
 
-    fn visit_unary_expr(&mut self, operator: Token, right: Box<Expr>) -> String {
-        let right = *right;
-        self.parenthesize(operator.lexeme(), &[right])
+    fn visit_logical_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _left: ExprId,
+        operator: &Token,
+        _right: ExprId,
+    ) -> String {
+        self.parenthesize(ast, id, operator.lexeme())
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _object: ExprId,
+        _bracket: &Token,
+        _index: ExprId,
+        _value: ExprId,
+    ) -> String {
+        self.parenthesize(ast, id, String::from("[]="))
+    }
+
+    /// A missing end of the slice is written `_`.
+    fn visit_slice_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        object: ExprId,
+        _bracket: &Token,
+        start: Option<ExprId>,
+        end: Option<ExprId>,
+    ) -> String {
+        let mut bound = |bound: Option<ExprId>| match bound {
+            Some(bound) => self.evaluate(ast, bound),
+            None => String::from("_"),
+        };
+        let (start, end) = (bound(start), bound(end));
+        format!("([:] {} {} {})", self.evaluate(ast, object), start, end)
+    }
+
+    fn visit_unary_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        operator: &Token,
+        _right: ExprId,
+    ) -> String {
+        self.parenthesize(ast, id, operator.lexeme())
+    }
+
+    fn visit_variable_expr(&mut self, _ast: &Ast, _id: ExprId, name: &Token) -> String {
+        name.lexeme()
+    }
+}
+
+impl StmtVisitor<String> for AstPrinter {
+    fn visit_block_stmt(&mut self, ast: &Ast, _id: StmtId, statements: &[StmtId]) -> String {
+        let mut block = String::from("(block");
+        for statement in self.statements(ast, statements) {
+            block.push(' ');
+            block.push_str(&statement);
+        }
+        block.push(')');
+        block
+    }
+
+    fn visit_break_stmt(&mut self, _ast: &Ast, _id: StmtId, _keyword: &Token) -> String {
+        String::from("(break)")
+    }
+
+    fn visit_continue_stmt(&mut self, _ast: &Ast, _id: StmtId, _keyword: &Token) -> String {
+        String::from("(continue)")
+    }
+
+    fn visit_expression_stmt(&mut self, ast: &Ast, _id: StmtId, expression: ExprId) -> String {
+        format!("(; {})", self.evaluate(ast, expression))
+    }
+
+    fn visit_for_in_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        iterable: ExprId,
+        body: StmtId,
+    ) -> String {
+        format!(
+            "(for {} {} {})",
+            name.lexeme(),
+            self.evaluate(ast, iterable),
+            self.execute(ast, body)
+        )
+    }
+
+    fn visit_function_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        params: &[Token],
+        body: &[StmtId],
+    ) -> String {
+        self.function(ast, format!("fun {}", name.lexeme()), params, body)
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        condition: ExprId,
+        then_branch: StmtId,
+        else_branch: Option<StmtId>,
+    ) -> String {
+        let mut statement = format!(
+            "(if {} {}",
+            self.evaluate(ast, condition),
+            self.execute(ast, then_branch)
+        );
+        if let Some(else_branch) = else_branch {
+            statement.push(' ');
+            statement.push_str(&self.execute(ast, else_branch));
+        }
+        statement.push(')');
+        statement
+    }
+
+    fn visit_print_stmt(&mut self, ast: &Ast, _id: StmtId, expression: ExprId) -> String {
+        format!("(print {})", self.evaluate(ast, expression))
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        _keyword: &Token,
+        value: Option<ExprId>,
+    ) -> String {
+        match value {
+            Some(value) => format!("(return {})", self.evaluate(ast, value)),
+            None => String::from("(return)"),
+        }
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        _keyword: &Token,
+        value: ExprId,
+    ) -> String {
+        format!("(throw {})", self.evaluate(ast, value))
+    }
+
+    fn visit_try_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        body: StmtId,
+        name: Option<&Token>,
+        handler: Option<StmtId>,
+        finalizer: Option<StmtId>,
+    ) -> String {
+        let mut statement = format!("(try {}", self.execute(ast, body));
+        if let (Some(name), Some(handler)) = (name, handler) {
+            let handler = self.execute(ast, handler);
+            statement.push_str(&format!(" (catch {} {})", name.lexeme(), handler));
+        }
+        if let Some(finalizer) = finalizer {
+            statement.push_str(&format!(" (finally {})", self.execute(ast, finalizer)));
+        }
+        statement.push(')');
+        statement
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        initializer: Option<ExprId>,
+    ) -> String {
+        match initializer {
+            Some(initializer) => {
+                format!(
+                    "(var {} {})",
+                    name.lexeme(),
+                    self.evaluate(ast, initializer)
+                )
+            }
+            None => format!("(var {})", name.lexeme()),
+        }
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        condition: ExprId,
+        body: StmtId,
+        increment: Option<ExprId>,
+    ) -> String {
+        let mut statement = format!("(while {}", self.evaluate(ast, condition));
+        if let Some(increment) = increment {
+            statement.push_str(&format!(" (then {})", self.evaluate(ast, increment)));
+        }
+        statement.push_str(&format!(" {})", self.execute(ast, body)));
+        statement
     }
 }
@@ -0,0 +1,423 @@
+/// A `DotPrinter` renders an AST as a Graphviz DOT digraph.
+/// Each node is labelled with its operator or value and the line it starts on,
+/// and has an edge to each of its children. The statements of a program hang off
+/// a single `program` node.
+#[derive(Default)]
+pub struct DotPrinter {
+    lines: Vec<String>,
+}
+
+use crate::ast::{Ast, ExprId, NodeId, StmtId};
+use crate::expr::{walk_expr, ExprVisitor};
+use crate::stmt::StmtVisitor;
+use crate::token::{Literal, Token};
+
+impl DotPrinter {
+    pub fn new() -> Self {
+        DotPrinter { lines: vec![] }
+    }
+
+    /// `print` renders the tree under the root expression of `ast`, or else
+    /// under its statements.
+    pub fn print(&mut self, ast: &Ast) -> String {
+        self.lines.clear();
+        if let Some(root) = ast.root() {
+            self.evaluate(ast, root);
+        } else {
+            self.lines
+                .push(String::from("program [label=\"program\"];"));
+            for &statement in ast.statements() {
+                let child = self.execute(ast, statement);
+                self.lines.push(format!("program -> {};", child));
+            }
+        }
+        let mut graph = String::from("digraph ast {\n");
+        for line in &self.lines {
+            graph.push_str("    ");
+            graph.push_str(line);
+            graph.push('\n');
+        }
+        graph.push('}');
+        graph
+    }
+
+    /// `node` declares `id` with `label` and links it to each of its children,
+    /// returning the name of the node in the graph.
+    fn node(&mut self, ast: &Ast, id: ExprId, label: &str) -> String {
+        let name = format!("n{}", id.index());
+        let label = format!("{} (line {})", label, ast.span(id).line());
+        self.lines
+            .push(format!("{} [label=\"{}\"];", name, Self::escape(&label)));
+        for child in walk_expr(self, ast, id) {
+            self.lines.push(format!("{} -> {};", name, child));
+        }
+        name
+    }
+
+    /// `stmt_node` declares the statement `id` like `node` does an expression, given
+    /// the names of its children.
+    fn stmt_node(&mut self, ast: &Ast, id: StmtId, label: &str, children: Vec<String>) -> String {
+        let name = format!("s{}", id.index());
+        let label = format!("{} (line {})", label, ast.span(id).line());
+        self.lines
+            .push(format!("{} [label=\"{}\"];", name, Self::escape(&label)));
+        for child in children {
+            self.lines.push(format!("{} -> {};", name, child));
+        }
+        name
+    }
+
+    fn escape(label: &str) -> String {
+        label.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+}
+
+impl ExprVisitor<String> for DotPrinter {
+    fn visit_assign_expr(&mut self, ast: &Ast, id: ExprId, name: &Token, _value: ExprId) -> String {
+        self.node(ast, id, &format!("= {}", name.lexeme()))
+    }
+
+    fn visit_binary_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _left: ExprId,
+        operator: &Token,
+        _right: ExprId,
+    ) -> String {
+        self.node(ast, id, &operator.lexeme())
+    }
+
+    fn visit_call_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _callee: ExprId,
+        _paren: &Token,
+        _arguments: &[ExprId],
+    ) -> String {
+        self.node(ast, id, "call")
+    }
+
+    fn visit_comma_expr(&mut self, ast: &Ast, id: ExprId, _left: ExprId, _right: ExprId) -> String {
+        self.node(ast, id, ",")
+    }
+
+    fn visit_conditional_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _condition: ExprId,
+        _then_branch: ExprId,
+        _else_branch: ExprId,
+    ) -> String {
+        self.node(ast, id, "?:")
+    }
+
+    fn visit_get_expr(&mut self, ast: &Ast, id: ExprId, _object: ExprId, name: &Token) -> String {
+        self.node(ast, id, &format!(". {}", name.lexeme()))
+    }
+
+    fn visit_grouping_expr(&mut self, ast: &Ast, id: ExprId, _expression: ExprId) -> String {
+        self.node(ast, id, "group")
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _object: ExprId,
+        _bracket: &Token,
+        _index: ExprId,
+    ) -> String {
+        self.node(ast, id, "[]")
+    }
+
+    /// A lambda's body is made of statements, which `node` does not know about,
+    /// so they are linked here.
+    fn visit_lambda_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _keyword: &Token,
+        params: &[Token],
+        body: &[StmtId],
+    ) -> String {
+        let params: Vec<String> = params.iter().map(Token::lexeme).collect();
+        let name = self.node(ast, id, &format!("fun ({})", params.join(", ")));
+        for &statement in body {
+            let child = self.execute(ast, statement);
+            self.lines.push(format!("{} -> {};", name, child));
+        }
+        name
+    }
+
+    fn visit_list_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _bracket: &Token,
+        _elements: &[ExprId],
+    ) -> String {
+        self.node(ast, id, "list")
+    }
+
+    fn visit_map_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _brace: &Token,
+        _entries: &[ExprId],
+    ) -> String {
+        self.node(ast, id, "map")
+    }
+
+    fn visit_literal_expr(&mut self, ast: &Ast, id: ExprId, value: &Literal) -> String {
+        let label = match value {
+            Literal::LoxString(value) => format!("\"{}\"", value),
+            other => other.to_string(),
+        };
+        self.node(ast, id, &label)
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _left: ExprId,
+        operator: &Token,
+        _right: ExprId,
+    ) -> String {
+        self.node(ast, id, &operator.lexeme())
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _object: ExprId,
+        _bracket: &Token,
+        _index: ExprId,
+        _value: ExprId,
+    ) -> String {
+        self.node(ast, id, "[]=")
+    }
+
+    /// The label shows which ends of the slice are given, since only those are children.
+    fn visit_slice_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _object: ExprId,
+        _bracket: &Token,
+        start: Option<ExprId>,
+        end: Option<ExprId>,
+    ) -> String {
+        let label = format!(
+            "[{}:{}]",
+            if start.is_some() { "start" } else { "" },
+            if end.is_some() { "end" } else { "" }
+        );
+        self.node(ast, id, &label)
+    }
+
+    fn visit_unary_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        operator: &Token,
+        _right: ExprId,
+    ) -> String {
+        self.node(ast, id, &operator.lexeme())
+    }
+
+    fn visit_variable_expr(&mut self, ast: &Ast, id: ExprId, name: &Token) -> String {
+        self.node(ast, id, &name.lexeme())
+    }
+}
+
+impl StmtVisitor<String> for DotPrinter {
+    fn visit_block_stmt(&mut self, ast: &Ast, id: StmtId, statements: &[StmtId]) -> String {
+        let children = statements
+            .iter()
+            .map(|&statement| self.execute(ast, statement))
+            .collect();
+        self.stmt_node(ast, id, "block", children)
+    }
+
+    fn visit_break_stmt(&mut self, ast: &Ast, id: StmtId, _keyword: &Token) -> String {
+        self.stmt_node(ast, id, "break", vec![])
+    }
+
+    fn visit_continue_stmt(&mut self, ast: &Ast, id: StmtId, _keyword: &Token) -> String {
+        self.stmt_node(ast, id, "continue", vec![])
+    }
+
+    fn visit_expression_stmt(&mut self, ast: &Ast, id: StmtId, expression: ExprId) -> String {
+        let children = vec![self.evaluate(ast, expression)];
+        self.stmt_node(ast, id, ";", children)
+    }
+
+    fn visit_for_in_stmt(
+        &mut self,
+        ast: &Ast,
+        id: StmtId,
+        name: &Token,
+        iterable: ExprId,
+        body: StmtId,
+    ) -> String {
+        let children = vec![self.evaluate(ast, iterable), self.execute(ast, body)];
+        self.stmt_node(ast, id, &format!("for {} in", name.lexeme()), children)
+    }
+
+    fn visit_function_stmt(
+        &mut self,
+        ast: &Ast,
+        id: StmtId,
+        name: &Token,
+        params: &[Token],
+        body: &[StmtId],
+    ) -> String {
+        let children = body
+            .iter()
+            .map(|&statement| self.execute(ast, statement))
+            .collect();
+        let params: Vec<String> = params.iter().map(Token::lexeme).collect();
+        let label = format!("fun {}({})", name.lexeme(), params.join(", "));
+        self.stmt_node(ast, id, &label, children)
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        ast: &Ast,
+        id: StmtId,
+        condition: ExprId,
+        then_branch: StmtId,
+        else_branch: Option<StmtId>,
+    ) -> String {
+        let mut children = vec![
+            self.evaluate(ast, condition),
+            self.execute(ast, then_branch),
+        ];
+        children.extend(else_branch.map(|else_branch| self.execute(ast, else_branch)));
+        self.stmt_node(ast, id, "if", children)
+    }
+
+    fn visit_print_stmt(&mut self, ast: &Ast, id: StmtId, expression: ExprId) -> String {
+        let children = vec![self.evaluate(ast, expression)];
+        self.stmt_node(ast, id, "print", children)
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        ast: &Ast,
+        id: StmtId,
+        _keyword: &Token,
+        value: Option<ExprId>,
+    ) -> String {
+        let children = value
+            .map(|value| self.evaluate(ast, value))
+            .into_iter()
+            .collect();
+        self.stmt_node(ast, id, "return", children)
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        ast: &Ast,
+        id: StmtId,
+        _keyword: &Token,
+        value: ExprId,
+    ) -> String {
+        let children = vec![self.evaluate(ast, value)];
+        self.stmt_node(ast, id, "throw", children)
+    }
+
+    fn visit_try_stmt(
+        &mut self,
+        ast: &Ast,
+        id: StmtId,
+        body: StmtId,
+        name: Option<&Token>,
+        handler: Option<StmtId>,
+        finalizer: Option<StmtId>,
+    ) -> String {
+        let mut children = vec![self.execute(ast, body)];
+        children.extend(handler.map(|handler| self.execute(ast, handler)));
+        children.extend(finalizer.map(|finalizer| self.execute(ast, finalizer)));
+        let label = match name {
+            Some(name) => format!("try (catch {})", name.lexeme()),
+            None => String::from("try"),
+        };
+        self.stmt_node(ast, id, &label, children)
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        ast: &Ast,
+        id: StmtId,
+        name: &Token,
+        initializer: Option<ExprId>,
+    ) -> String {
+        let children = initializer
+            .map(|initializer| self.evaluate(ast, initializer))
+            .into_iter()
+            .collect();
+        self.stmt_node(ast, id, &format!("var {}", name.lexeme()), children)
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        ast: &Ast,
+        id: StmtId,
+        condition: ExprId,
+        body: StmtId,
+        increment: Option<ExprId>,
+    ) -> String {
+        let mut children = vec![self.evaluate(ast, condition), self.execute(ast, body)];
+        children.extend(increment.map(|increment| self.evaluate(ast, increment)));
+        self.stmt_node(ast, id, "while", children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn dot(source: &str) -> String {
+        DotPrinter::new().print(&testing::parse(source))
+    }
+
+    #[test]
+    fn expressions_link_to_their_operands() {
+        assert_eq!(
+            dot("-(1 + 2)"),
+            "digraph ast {\n    \
+             n4 [label=\"- (line 1)\"];\n    \
+             n3 [label=\"group (line 1)\"];\n    \
+             n2 [label=\"+ (line 1)\"];\n    \
+             n0 [label=\"1 (line 1)\"];\n    \
+             n1 [label=\"2 (line 1)\"];\n    \
+             n2 -> n0;\n    \
+             n2 -> n1;\n    \
+             n3 -> n2;\n    \
+             n4 -> n3;\n\
+             }"
+        );
+    }
+
+    #[test]
+    fn programs_match_the_snapshot() {
+        let source = "var f = fun (x) { return x * 2; };\nif (f(1) > 1) print \"a\\b\";";
+        assert_eq!(
+            dot(source),
+            include_str!("../tests/fixtures/graph.dot").trim_end()
+        );
+    }
+
+    #[test]
+    fn labels_are_escaped() {
+        assert_eq!(DotPrinter::escape("\"a\\b\""), "\\\"a\\\\b\\\"");
+    }
+}
@@ -0,0 +1,63 @@
+use crate::evaluator::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// An `Environment` maps the variables of one scope to their values, and refers
+/// to the scope enclosing it, if any.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment::default()
+    }
+
+    /// `with_enclosing` creates a scope nested inside `enclosing`.
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// `define` binds `name` in this scope, replacing any existing binding.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_owned(), value);
+    }
+
+    /// `get` looks `name` up in this scope and then in each enclosing one.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.enclosing.as_ref()?.borrow().get(name),
+        }
+    }
+
+    /// `assign` updates the innermost binding of `name`, returning `false` if there
+    /// is none.
+    pub fn assign(&mut self, name: &str, value: Value) -> bool {
+        if let Some(slot) = self.values.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => false,
+        }
+    }
+
+    /// `values` lists the bindings of this scope alone, sorted by name.
+    pub fn values(&self) -> Vec<(String, Value)> {
+        let mut values: Vec<_> = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+        values
+    }
+}
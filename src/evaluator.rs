@@ -0,0 +1,1629 @@
+/// An `Evaluator` runs a program by walking its AST.
+/// Globals outlive a single call to `interpret`, so a REPL session keeps them.
+///
+/// Each Lox call recurses in the evaluator, so how deeply calls may nest is
+/// bounded by the stack of the thread running it. The default limit,
+/// `MAX_CALL_DEPTH`, fits the 8MiB a main thread usually has, as long as function
+/// bodies don't nest blocks very deeply. A thread with `STACK_SIZE` of stack can
+/// raise it to `DEEP_CALL_DEPTH` with `set_max_call_depth`.
+pub struct Evaluator {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    /// The program whose nodes are being evaluated, which functions created from
+    /// them keep hold of.
+    ast: Rc<Ast>,
+    file: String,
+    /// How many calls are in progress.
+    depth: usize,
+    /// How deeply calls may nest before a program is taken to be recursing
+    /// without end.
+    max_depth: usize,
+}
+
+/// How deeply calls may nest by default. A call takes between 2KB and 8KB of
+/// stack in a release build, depending on how deeply its body nests, and up to
+/// about 26KB in a debug build.
+pub const MAX_CALL_DEPTH: usize = 256;
+
+/// How deeply calls may nest on a thread with `STACK_SIZE` of stack.
+pub const DEEP_CALL_DEPTH: usize = 4096;
+
+/// The native stack a thread running the evaluator needs to make `DEEP_CALL_DEPTH`
+/// nested calls.
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// The properties of an error caught as a value.
+pub const ERROR_PROPERTIES: &[&str] = &["message", "line"];
+
+use crate::ast::{Ast, ExprId, StmtId};
+use crate::environment::Environment;
+use crate::expr::ExprVisitor;
+use crate::iterator::{self, LoxIterator};
+use crate::list::{self, List};
+use crate::map::{self, Key, Map, MapRef};
+use crate::stmt::StmtVisitor;
+use crate::token::{Literal, Span, Token, TokenType};
+use std::cell::RefCell;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+/// A `Value` is anything a Lox expression can evaluate to.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    LoxString(String),
+    Function(Rc<Function>),
+    Native(Rc<Native>),
+    List(List),
+    Map(MapRef),
+    Iterator(Rc<LoxIterator>),
+    /// The value a `catch` clause receives for a runtime error raised by the
+    /// evaluator itself, rather than by `throw`.
+    Error(Rc<ErrorObject>),
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::LoxString(a), Value::LoxString(b)) => a == b,
+            // Functions, collections, iterators and error objects are only equal to themselves.
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            (Value::Iterator(a), Value::Iterator(b)) => Rc::ptr_eq(a, b),
+            (Value::Error(a), Value::Error(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    /// `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+
+    /// The name of the value's type, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Boolean(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::LoxString(_) => "string",
+            Value::Function(_) | Value::Native(_) => "function",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Iterator(_) => "iterator",
+            Value::Error(_) => "error",
+        }
+    }
+
+    /// The names of the value's built-in properties.
+    pub fn properties(&self) -> &'static [&'static str] {
+        match self {
+            Value::List(_) => list::METHODS,
+            Value::Map(_) => map::METHODS,
+            Value::Iterator(_) => iterator::METHODS,
+            Value::Error(_) => ERROR_PROPERTIES,
+            _ => &[],
+        }
+    }
+
+    /// `repr` shows the value as an element of a list or map, where strings are
+    /// quoted so that `["a, b"]` is not taken for two elements.
+    pub fn repr(&self) -> String {
+        match self {
+            Value::LoxString(value) => format!("\"{}\"", value),
+            value => value.to_string(),
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::LoxString(value) => Value::LoxString(value.clone()),
+            Literal::Number(value) => Value::Number(*value),
+            Literal::Boolean(value) => Value::Boolean(*value),
+            Literal::Nil => Value::Nil,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::LoxString(value) => write!(f, "{}", value),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::Native(native) => write!(f, "{}", native),
+            Value::List(list) => display_once(Rc::as_ptr(list).cast(), "[...]", f, |f| {
+                write!(f, "[")?;
+                for (i, element) in list.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element.repr())?;
+                }
+                write!(f, "]")
+            }),
+            Value::Map(map) => display_once(Rc::as_ptr(map).cast(), "{...}", f, |f| {
+                write!(f, "{}", map.borrow())
+            }),
+            Value::Iterator(iterator) => write!(f, "{}", iterator),
+            Value::Error(error) => write!(f, "<error: {}>", error.message),
+        }
+    }
+}
+
+thread_local! {
+    /// The lists and maps being displayed, outermost first.
+    static DISPLAYING: RefCell<Vec<*const ()>> = const { RefCell::new(vec![]) };
+}
+
+/// `display_once` displays the list or map at `address` with `display`, unless
+/// it is already being displayed further out. Then it contains itself, and is
+/// shown as `cycle` rather than recursing without end.
+fn display_once(
+    address: *const (),
+    cycle: &str,
+    f: &mut fmt::Formatter,
+    display: impl FnOnce(&mut fmt::Formatter) -> fmt::Result,
+) -> fmt::Result {
+    if DISPLAYING.with(|displaying| displaying.borrow().contains(&address)) {
+        return write!(f, "{}", cycle);
+    }
+    DISPLAYING.with(|displaying| displaying.borrow_mut().push(address));
+    let result = display(f);
+    DISPLAYING.with(|displaying| displaying.borrow_mut().pop());
+    result
+}
+
+/// A `Function` is a function declaration or lambda, closed over the scope it was
+/// created in.
+pub struct Function {
+    /// The declared name, or `None` for a lambda.
+    name: Option<String>,
+    params: Vec<Token>,
+    body: Vec<StmtId>,
+    /// The program the body belongs to, kept alive for as long as the function.
+    ast: Rc<Ast>,
+    closure: Rc<RefCell<Environment>>,
+    /// The source the function was defined in, for stack traces.
+    file: String,
+}
+
+impl Function {
+    /// The name the function goes by in stack traces.
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("lambda")
+    }
+
+    pub fn arity(&self) -> usize {
+        self.params.len()
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<fn>"),
+        }
+    }
+}
+
+// Printing the closure could recurse forever, since a function is usually
+// reachable from its own closure.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// The body of a `Native`, given arguments whose number has been checked. An error
+/// is reported at the call.
+type NativeFn = dyn Fn(Vec<Value>) -> std::result::Result<Value, String>;
+
+/// A `Native` is a function implemented in Rust, such as a method bound to a list.
+pub struct Native {
+    name: String,
+    arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl Native {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        function: impl Fn(Vec<Value>) -> std::result::Result<Value, String> + 'static,
+    ) -> Self {
+        Native {
+            name: name.to_owned(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn call(&self, arguments: Vec<Value>) -> std::result::Result<Value, String> {
+        (self.function)(arguments)
+    }
+}
+
+impl fmt::Display for Native {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// An `ErrorObject` describes a caught runtime error. Lox code reads its
+/// `message` and `line` properties.
+#[derive(Debug, PartialEq)]
+pub struct ErrorObject {
+    message: String,
+    line: usize,
+}
+
+impl ErrorObject {
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+/// A `Frame` is one entry in the stack trace of a `RuntimeError`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    function: String,
+    file: String,
+    line: usize,
+}
+
+impl Frame {
+    pub fn new(function: &str, file: &str, line: usize) -> Self {
+        Frame {
+            function: function.to_owned(),
+            file: file.to_owned(),
+            line,
+        }
+    }
+
+    /// The name of the function, or `script` for top-level code.
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+/// A `RuntimeError` is raised while evaluating, and points at the operator
+/// that could not be applied and the expression it belongs to. A `throw`
+/// statement raises one too, carrying the thrown value.
+#[derive(Debug)]
+pub struct RuntimeError {
+    token: Box<Token>,
+    span: Span,
+    message: String,
+    thrown: Option<Value>,
+    frames: Vec<Frame>,
+    /// The line the error has reached in the frame it is unwinding through.
+    line: usize,
+}
+
+impl RuntimeError {
+    pub fn new(token: &Token, span: Span, message: &str) -> Self {
+        RuntimeError {
+            token: Box::new(token.clone()),
+            span,
+            message: message.to_owned(),
+            thrown: None,
+            frames: vec![],
+            line: token.line(),
+        }
+    }
+
+    /// `thrown` creates the error raised by a `throw` statement.
+    pub fn thrown(keyword: &Token, span: Span, value: Value) -> Self {
+        let mut error = RuntimeError::new(keyword, span, &format!("Uncaught exception: {}", value));
+        error.thrown = Some(value);
+        error
+    }
+
+    /// `value` is what a `catch` clause binds: the thrown value, or an error
+    /// object describing an error the evaluator raised.
+    pub fn value(&self) -> Value {
+        match &self.thrown {
+            Some(value) => value.clone(),
+            None => Value::Error(Rc::new(ErrorObject {
+                message: self.message.clone(),
+                line: self.token.line(),
+            })),
+        }
+    }
+
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    /// The span of the expression that failed.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The stack at the time of the error, innermost frame first.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// `push_frame` records that the error unwound out of `function`, at the line
+    /// it had reached there. `call` is the line of the call that led into
+    /// `function`, where the error goes on to unwind from; top-level code has none.
+    /// Frames are pushed from the innermost outwards.
+    pub fn push_frame(&mut self, function: &str, file: &str, call: Option<usize>) {
+        self.frames.push(Frame::new(function, file, self.line));
+        if let Some(call) = call {
+            self.line = call;
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, RuntimeError>;
+
+/// How control leaves a statement: by carrying on to the next one, by jumping
+/// out of the loop around it, or by returning from the function around it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator::new()
+    }
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals
+            .borrow_mut()
+            .define("range", Value::Native(Rc::new(Self::range())));
+        Evaluator {
+            environment: Rc::clone(&globals),
+            globals,
+            ast: Rc::new(Ast::new()),
+            file: String::new(),
+            depth: 0,
+            max_depth: MAX_CALL_DEPTH,
+        }
+    }
+
+    /// `range` is the native `range(start, end)`, which returns an iterator over the
+    /// numbers from `start` up to `end`.
+    fn range() -> Native {
+        Native::new("range", 2, |arguments| match arguments[..] {
+            [Value::Number(start), Value::Number(end)] => {
+                Ok(Value::Iterator(Rc::new(iterator::range(start, end))))
+            }
+            _ => Err(String::from("Range bounds must be numbers.")),
+        })
+    }
+
+    /// `set_file` names the source being run, for stack traces.
+    pub fn set_file(&mut self, file: &str) {
+        self.file = file.to_owned();
+    }
+
+    /// `set_max_call_depth` limits how deeply calls may nest. The thread running
+    /// the evaluator must have the stack for it: see `Evaluator`.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.globals.borrow_mut().define(name, value);
+    }
+
+    /// `globals` lists every global variable, sorted by name.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.globals.borrow().values()
+    }
+
+    /// `interpret` runs `ast`. A bare expression gives its value; a program of
+    /// statements gives `None`. Recursion past the call depth limit is a runtime
+    /// error, provided the thread has the stack the limit needs.
+    pub fn interpret(&mut self, ast: Rc<Ast>) -> Result<Option<Value>> {
+        self.ast = Rc::clone(&ast);
+        let result = match ast.root() {
+            Some(root) => self.evaluate(&ast, root).map(Some),
+            None => ast
+                .statements()
+                .iter()
+                .try_for_each(|&statement| self.execute(&ast, statement).map(|_| ()))
+                .map(|()| None),
+        };
+        result.map_err(|mut error| {
+            error.push_frame("script", &self.file, None);
+            error
+        })
+    }
+
+    /// `function` creates a function closed over the current environment.
+    fn function(&self, name: Option<&Token>, params: &[Token], body: &[StmtId]) -> Rc<Function> {
+        Rc::new(Function {
+            name: name.map(Token::lexeme),
+            params: params.to_vec(),
+            body: body.to_vec(),
+            ast: Rc::clone(&self.ast),
+            closure: Rc::clone(&self.environment),
+            file: self.file.clone(),
+        })
+    }
+
+    /// `call` runs the body of `function` with its parameters bound to `arguments`,
+    /// whose number has already been checked.
+    fn call(&mut self, function: &Function, arguments: Vec<Value>) -> Result<Value> {
+        let mut environment = Environment::with_enclosing(Rc::clone(&function.closure));
+        for (param, argument) in function.params.iter().zip(arguments) {
+            environment.define(&param.lexeme(), argument);
+        }
+        let caller = mem::replace(&mut self.ast, Rc::clone(&function.ast));
+        let result = self.execute_block(&function.ast, &function.body, environment);
+        self.ast = caller;
+        match result? {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(Value::Nil),
+        }
+    }
+
+    /// `execute_block` runs `statements` in `environment`, then restores the
+    /// current environment however they finished.
+    fn execute_block(
+        &mut self,
+        ast: &Ast,
+        statements: &[StmtId],
+        environment: Environment,
+    ) -> Result<Flow> {
+        let previous = mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = self.execute_all(ast, statements);
+        self.environment = previous;
+        result
+    }
+
+    /// `execute_all` runs `statements` in turn, stopping early at a jump.
+    fn execute_all(&mut self, ast: &Ast, statements: &[StmtId]) -> Result<Flow> {
+        for &statement in statements {
+            let flow = self.execute(ast, statement)?;
+            if flow != Flow::Normal {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// `iterator` gets an iterator over `value`, the `iterable` of a `for` loop.
+    /// Strings are iterated by character; anything else must have an `iterator()`
+    /// method returning a value with `hasNext()` and `next()` methods. A map can
+    /// take part in the protocol with functions stored under those names, in
+    /// place of a class: see the `iterator` module.
+    fn iterator(
+        &mut self,
+        ast: &Ast,
+        iterable: ExprId,
+        name: &Token,
+        value: Value,
+    ) -> Result<Value> {
+        if let Value::LoxString(string) = &value {
+            return Ok(Value::Iterator(Rc::new(iterator::characters(string))));
+        }
+        let method = Self::protocol_method(&value, "iterator").map_err(|_| {
+            RuntimeError::new(
+                name,
+                ast.span(iterable),
+                "Can only iterate over strings and values with an 'iterator' method.",
+            )
+        })?;
+        self.call_value(ast, iterable, name, method, vec![])
+    }
+
+    fn iterator_method(
+        ast: &Ast,
+        iterable: ExprId,
+        name: &Token,
+        iterator: &Value,
+        method: &str,
+    ) -> Result<Value> {
+        Self::protocol_method(iterator, method).map_err(|_| {
+            RuntimeError::new(
+                name,
+                ast.span(iterable),
+                &format!("Iterator must have a '{}' method.", method),
+            )
+        })
+    }
+
+    /// `protocol_method` looks up the method `name` of the iterator protocol on
+    /// `object`. A map's entry for `name` comes before its built-in method when it
+    /// is a function, so that Lox code can implement the protocol; any other entry
+    /// is plain data, and the map is iterated as usual.
+    fn protocol_method(object: &Value, name: &str) -> std::result::Result<Value, String> {
+        if let Value::Map(map) = object {
+            if let Some(method @ (Value::Function(_) | Value::Native(_))) =
+                map.borrow().get(&Key::LoxString(name.to_owned()))
+            {
+                return Ok(method.clone());
+            }
+        }
+        Self::property(object, name)
+    }
+
+    /// `sliced` evaluates `object` as the list in a slice expression.
+    fn sliced(&mut self, ast: &Ast, id: ExprId, object: ExprId, bracket: &Token) -> Result<List> {
+        match self.evaluate(ast, object)? {
+            Value::List(list) => Ok(list),
+            _ => Err(RuntimeError::new(
+                bracket,
+                ast.span(id),
+                "Only lists can be sliced.",
+            )),
+        }
+    }
+
+    /// `key` converts `value` to a map key, failing for values that can't be hashed.
+    fn key(ast: &Ast, id: ExprId, token: &Token, value: &Value) -> Result<Key> {
+        Key::try_from(value).map_err(|message| RuntimeError::new(token, ast.span(id), &message))
+    }
+
+    /// `call_value` calls `callee` with `arguments`, reporting errors at `token`.
+    fn call_value(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        token: &Token,
+        callee: Value,
+        arguments: Vec<Value>,
+    ) -> Result<Value> {
+        let arity = match &callee {
+            Value::Function(function) => function.arity(),
+            Value::Native(native) => native.arity(),
+            _ => {
+                return Err(RuntimeError::new(
+                    token,
+                    ast.span(id),
+                    "Can only call functions.",
+                ))
+            }
+        };
+        if arguments.len() != arity {
+            return Err(RuntimeError::new(
+                token,
+                ast.span(id),
+                &format!("Expected {} arguments but got {}.", arity, arguments.len()),
+            ));
+        }
+        let function = match callee {
+            Value::Native(native) => {
+                return native
+                    .call(arguments)
+                    .map_err(|message| RuntimeError::new(token, ast.span(id), &message))
+            }
+            Value::Function(function) => function,
+            _ => unreachable!("only functions have an arity"),
+        };
+        if self.depth == self.max_depth {
+            return Err(RuntimeError::new(token, ast.span(id), "Stack overflow."));
+        }
+        self.depth += 1;
+        let result = self.call(&function, arguments);
+        self.depth -= 1;
+        result.map_err(|mut error| {
+            error.push_frame(function.name(), &function.file, Some(token.line()));
+            error
+        })
+    }
+
+    /// `property` looks up the property `name` of `object`. Every name it finds
+    /// is listed by `Value::properties`.
+    fn property(object: &Value, name: &str) -> std::result::Result<Value, String> {
+        let property = match object {
+            Value::Error(error) => match name {
+                "message" => Some(Value::LoxString(error.message.clone())),
+                "line" => Some(Value::Number(error.line as f64)),
+                _ => None,
+            },
+            Value::List(list) => {
+                list::method(list, name).map(|method| Value::Native(Rc::new(method)))
+            }
+            Value::Map(map) => map::method(map, name).map(|method| Value::Native(Rc::new(method))),
+            Value::Iterator(iterator) => {
+                iterator::method(iterator, name).map(|method| Value::Native(Rc::new(method)))
+            }
+            _ => {
+                return Err(String::from(
+                    "Only lists, maps, iterators and errors have properties.",
+                ))
+            }
+        };
+        property.ok_or_else(|| format!("Undefined property '{}'.", name))
+    }
+
+    fn undefined_variable(ast: &Ast, id: ExprId, name: &Token) -> RuntimeError {
+        RuntimeError::new(
+            name,
+            ast.span(id),
+            &format!("Undefined variable '{}'.", name.lexeme()),
+        )
+    }
+
+    fn number_operand(operator: &Token, span: Span, operand: Value) -> Result<f64> {
+        match operand {
+            Value::Number(value) => Ok(value),
+            _ => Err(RuntimeError::new(
+                operator,
+                span,
+                "Operand must be a number.",
+            )),
+        }
+    }
+
+    fn number_operands(
+        operator: &Token,
+        span: Span,
+        left: Value,
+        right: Value,
+    ) -> Result<(f64, f64)> {
+        match (left, right) {
+            (Value::Number(left), Value::Number(right)) => Ok((left, right)),
+            _ => Err(RuntimeError::new(
+                operator,
+                span,
+                "Operands must be numbers.",
+            )),
+        }
+    }
+}
+
+impl ExprVisitor<Result<Value>> for Evaluator {
+    fn visit_assign_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        name: &Token,
+        value: ExprId,
+    ) -> Result<Value> {
+        let value = self.evaluate(ast, value)?;
+        if self
+            .environment
+            .borrow_mut()
+            .assign(&name.lexeme(), value.clone())
+        {
+            Ok(value)
+        } else {
+            Err(Self::undefined_variable(ast, id, name))
+        }
+    }
+
+    fn visit_binary_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        left: ExprId,
+        operator: &Token,
+        right: ExprId,
+    ) -> Result<Value> {
+        let left = self.evaluate(ast, left)?;
+        let right = self.evaluate(ast, right)?;
+
+        let value = match operator.token_type() {
+            TokenType::EqualEqual => Value::Boolean(left == right),
+            TokenType::BangEqual => Value::Boolean(left != right),
+            TokenType::Plus => match (left, right) {
+                (Value::Number(left), Value::Number(right)) => Value::Number(left + right),
+                (Value::LoxString(left), Value::LoxString(right)) => {
+                    Value::LoxString(left + &right)
+                }
+                _ => {
+                    return Err(RuntimeError::new(
+                        operator,
+                        ast.span(id),
+                        "Operands must be two numbers or two strings.",
+                    ))
+                }
+            },
+            token_type => {
+                let (left, right) = Self::number_operands(operator, ast.span(id), left, right)?;
+                match token_type {
+                    TokenType::Minus => Value::Number(left - right),
+                    TokenType::Star => Value::Number(left * right),
+                    TokenType::Slash => Value::Number(left / right),
+                    TokenType::Greater => Value::Boolean(left > right),
+                    TokenType::GreaterEqual => Value::Boolean(left >= right),
+                    TokenType::Less => Value::Boolean(left < right),
+                    TokenType::LessEqual => Value::Boolean(left <= right),
+                    _ => unreachable!("{} is not a binary operator", operator),
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    fn visit_call_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        callee: ExprId,
+        paren: &Token,
+        arguments: &[ExprId],
+    ) -> Result<Value> {
+        let callee = self.evaluate(ast, callee)?;
+        let arguments = arguments
+            .iter()
+            .map(|&argument| self.evaluate(ast, argument))
+            .collect::<Result<Vec<_>>>()?;
+        self.call_value(ast, id, paren, callee, arguments)
+    }
+
+    fn visit_comma_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        left: ExprId,
+        right: ExprId,
+    ) -> Result<Value> {
+        self.evaluate(ast, left)?;
+        self.evaluate(ast, right)
+    }
+
+    fn visit_conditional_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        condition: ExprId,
+        then_branch: ExprId,
+        else_branch: ExprId,
+    ) -> Result<Value> {
+        if self.evaluate(ast, condition)?.is_truthy() {
+            self.evaluate(ast, then_branch)
+        } else {
+            self.evaluate(ast, else_branch)
+        }
+    }
+
+    fn visit_get_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        object: ExprId,
+        name: &Token,
+    ) -> Result<Value> {
+        let object = self.evaluate(ast, object)?;
+        Self::property(&object, &name.lexeme())
+            .map_err(|message| RuntimeError::new(name, ast.span(id), &message))
+    }
+
+    fn visit_grouping_expr(&mut self, ast: &Ast, _id: ExprId, expression: ExprId) -> Result<Value> {
+        self.evaluate(ast, expression)
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        object: ExprId,
+        bracket: &Token,
+        index: ExprId,
+    ) -> Result<Value> {
+        let object = self.evaluate(ast, object)?;
+        let index = self.evaluate(ast, index)?;
+        let error = |message: String| RuntimeError::new(bracket, ast.span(id), &message);
+        match object {
+            Value::List(list) => {
+                let list = list.borrow();
+                let index = list::index(&index, list.len()).map_err(error)?;
+                Ok(list[index].clone())
+            }
+            Value::Map(map) => {
+                let key = Self::key(ast, id, bracket, &index)?;
+                let value = map.borrow().get(&key).cloned();
+                value.ok_or_else(|| error(format!("Map has no key {}.", index.repr())))
+            }
+            _ => Err(error(String::from("Only lists and maps can be indexed."))),
+        }
+    }
+
+    fn visit_lambda_expr(
+        &mut self,
+        _ast: &Ast,
+        _id: ExprId,
+        _keyword: &Token,
+        params: &[Token],
+        body: &[StmtId],
+    ) -> Result<Value> {
+        Ok(Value::Function(self.function(None, params, body)))
+    }
+
+    fn visit_list_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        _bracket: &Token,
+        elements: &[ExprId],
+    ) -> Result<Value> {
+        let elements = elements
+            .iter()
+            .map(|&element| self.evaluate(ast, element))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Value::List(Rc::new(RefCell::new(elements))))
+    }
+
+    fn visit_map_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        brace: &Token,
+        entries: &[ExprId],
+    ) -> Result<Value> {
+        let mut map = Map::new();
+        for entry in entries.chunks(2) {
+            let key = self.evaluate(ast, entry[0])?;
+            let key = Self::key(ast, id, brace, &key)?;
+            let value = self.evaluate(ast, entry[1])?;
+            map.insert(key, value);
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    }
+
+    fn visit_literal_expr(&mut self, _ast: &Ast, _id: ExprId, value: &Literal) -> Result<Value> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        left: ExprId,
+        operator: &Token,
+        right: ExprId,
+    ) -> Result<Value> {
+        // The right operand is only evaluated when the left doesn't settle the
+        // result, and the value of whichever operand decided it is the result.
+        let left = self.evaluate(ast, left)?;
+        let settled = match operator.token_type() {
+            TokenType::Or => left.is_truthy(),
+            TokenType::And => !left.is_truthy(),
+            _ => unreachable!("{} is not a logical operator", operator),
+        };
+        if settled {
+            return Ok(left);
+        }
+        self.evaluate(ast, right)
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        object: ExprId,
+        bracket: &Token,
+        index: ExprId,
+        value: ExprId,
+    ) -> Result<Value> {
+        let object = self.evaluate(ast, object)?;
+        let index = self.evaluate(ast, index)?;
+        let value = self.evaluate(ast, value)?;
+        match object {
+            Value::List(list) => {
+                let mut list = list.borrow_mut();
+                let index = list::index(&index, list.len())
+                    .map_err(|message| RuntimeError::new(bracket, ast.span(id), &message))?;
+                list[index] = value.clone();
+            }
+            Value::Map(map) => {
+                let key = Self::key(ast, id, bracket, &index)?;
+                map.borrow_mut().insert(key, value.clone());
+            }
+            _ => {
+                return Err(RuntimeError::new(
+                    bracket,
+                    ast.span(id),
+                    "Only lists and maps can be indexed.",
+                ))
+            }
+        }
+        Ok(value)
+    }
+
+    fn visit_slice_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        object: ExprId,
+        bracket: &Token,
+        start: Option<ExprId>,
+        end: Option<ExprId>,
+    ) -> Result<Value> {
+        let list = self.sliced(ast, id, object, bracket)?;
+        let start = start.map(|start| self.evaluate(ast, start)).transpose()?;
+        let end = end.map(|end| self.evaluate(ast, end)).transpose()?;
+
+        let list = list.borrow();
+        let error = |message: String| RuntimeError::new(bracket, ast.span(id), &message);
+        let start = match start {
+            Some(start) => list::bound(&start, list.len()).map_err(error)?,
+            None => 0,
+        };
+        let end = match end {
+            Some(end) => list::bound(&end, list.len()).map_err(error)?,
+            None => list.len(),
+        };
+        if start > end {
+            return Err(error(format!(
+                "Slice start {} is after its end {}.",
+                start, end
+            )));
+        }
+        Ok(Value::List(Rc::new(RefCell::new(
+            list[start..end].to_vec(),
+        ))))
+    }
+
+    fn visit_unary_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        operator: &Token,
+        right: ExprId,
+    ) -> Result<Value> {
+        let right = self.evaluate(ast, right)?;
+        match operator.token_type() {
+            TokenType::Minus => Ok(Value::Number(-Self::number_operand(
+                operator,
+                ast.span(id),
+                right,
+            )?)),
+            TokenType::Bang => Ok(Value::Boolean(!right.is_truthy())),
+            _ => unreachable!("{} is not a unary operator", operator),
+        }
+    }
+
+    fn visit_variable_expr(&mut self, ast: &Ast, id: ExprId, name: &Token) -> Result<Value> {
+        self.environment
+            .borrow()
+            .get(&name.lexeme())
+            .ok_or_else(|| Self::undefined_variable(ast, id, name))
+    }
+}
+
+impl StmtVisitor<Result<Flow>> for Evaluator {
+    fn visit_block_stmt(&mut self, ast: &Ast, _id: StmtId, statements: &[StmtId]) -> Result<Flow> {
+        let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        self.execute_block(ast, statements, environment)
+    }
+
+    fn visit_break_stmt(&mut self, _ast: &Ast, _id: StmtId, _keyword: &Token) -> Result<Flow> {
+        Ok(Flow::Break)
+    }
+
+    fn visit_continue_stmt(&mut self, _ast: &Ast, _id: StmtId, _keyword: &Token) -> Result<Flow> {
+        Ok(Flow::Continue)
+    }
+
+    fn visit_expression_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        expression: ExprId,
+    ) -> Result<Flow> {
+        self.evaluate(ast, expression)?;
+        Ok(Flow::Normal)
+    }
+
+    fn visit_for_in_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        iterable: ExprId,
+        body: StmtId,
+    ) -> Result<Flow> {
+        let value = self.evaluate(ast, iterable)?;
+        let iterator = self.iterator(ast, iterable, name, value)?;
+        loop {
+            let value = match &iterator {
+                Value::Iterator(iterator) => match iterator.next() {
+                    Some(value) => value,
+                    None => break,
+                },
+                iterator => {
+                    let method = Self::iterator_method(ast, iterable, name, iterator, "hasNext")?;
+                    if !self
+                        .call_value(ast, iterable, name, method, vec![])?
+                        .is_truthy()
+                    {
+                        break;
+                    }
+                    let method = Self::iterator_method(ast, iterable, name, iterator, "next")?;
+                    self.call_value(ast, iterable, name, method, vec![])?
+                }
+            };
+            // Each pass gets its own variable, so closures made in the body see the
+            // value of their own pass.
+            let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+            environment.define(&name.lexeme(), value);
+            match self.execute_block(ast, &[body], environment)? {
+                Flow::Break => break,
+                Flow::Return(value) => return Ok(Flow::Return(value)),
+                Flow::Normal | Flow::Continue => {}
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn visit_function_stmt(
+        &mut self,
+        _ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        params: &[Token],
+        body: &[StmtId],
+    ) -> Result<Flow> {
+        let function = self.function(Some(name), params, body);
+        self.environment
+            .borrow_mut()
+            .define(&name.lexeme(), Value::Function(function));
+        Ok(Flow::Normal)
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        condition: ExprId,
+        then_branch: StmtId,
+        else_branch: Option<StmtId>,
+    ) -> Result<Flow> {
+        if self.evaluate(ast, condition)?.is_truthy() {
+            self.execute(ast, then_branch)
+        } else if let Some(else_branch) = else_branch {
+            self.execute(ast, else_branch)
+        } else {
+            Ok(Flow::Normal)
+        }
+    }
+
+    fn visit_print_stmt(&mut self, ast: &Ast, _id: StmtId, expression: ExprId) -> Result<Flow> {
+        println!("{}", self.evaluate(ast, expression)?);
+        Ok(Flow::Normal)
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        _keyword: &Token,
+        value: Option<ExprId>,
+    ) -> Result<Flow> {
+        let value = match value {
+            Some(value) => self.evaluate(ast, value)?,
+            None => Value::Nil,
+        };
+        Ok(Flow::Return(value))
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        ast: &Ast,
+        id: StmtId,
+        keyword: &Token,
+        value: ExprId,
+    ) -> Result<Flow> {
+        let value = self.evaluate(ast, value)?;
+        Err(RuntimeError::thrown(keyword, ast.span(id), value))
+    }
+
+    fn visit_try_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        body: StmtId,
+        name: Option<&Token>,
+        handler: Option<StmtId>,
+        finalizer: Option<StmtId>,
+    ) -> Result<Flow> {
+        let result = match (self.execute(ast, body), name, handler) {
+            (Err(error), Some(name), Some(handler)) => {
+                // The caught value lives in a scope of its own around the handler.
+                let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                environment.define(&name.lexeme(), error.value());
+                self.execute_block(ast, &[handler], environment)
+            }
+            (result, _, _) => result,
+        };
+        // An error or jump in the finalizer replaces whatever the body or handler
+        // was doing.
+        if let Some(finalizer) = finalizer {
+            let flow = self.execute(ast, finalizer)?;
+            if flow != Flow::Normal {
+                return Ok(flow);
+            }
+        }
+        result
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        initializer: Option<ExprId>,
+    ) -> Result<Flow> {
+        let value = match initializer {
+            Some(initializer) => self.evaluate(ast, initializer)?,
+            None => Value::Nil,
+        };
+        self.environment.borrow_mut().define(&name.lexeme(), value);
+        Ok(Flow::Normal)
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        condition: ExprId,
+        body: StmtId,
+        increment: Option<ExprId>,
+    ) -> Result<Flow> {
+        while self.evaluate(ast, condition)?.is_truthy() {
+            match self.execute(ast, body)? {
+                Flow::Break => break,
+                Flow::Return(value) => return Ok(Flow::Return(value)),
+                Flow::Normal | Flow::Continue => {}
+            }
+            // A `for` loop's increment runs after `continue` too.
+            if let Some(increment) = increment {
+                self.evaluate(ast, increment)?;
+            }
+        }
+        Ok(Flow::Normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn run(evaluator: &mut Evaluator, source: &str) -> Result<Option<Value>> {
+        evaluator.interpret(Rc::new(testing::parse(source)))
+    }
+
+    /// `value` runs `program`, then gives the value of `expression` in the globals
+    /// it leaves behind.
+    fn value(program: &str, expression: &str) -> Value {
+        let mut evaluator = Evaluator::new();
+        if let Err(error) = run(&mut evaluator, program) {
+            panic!("running {}: {}", program, error.message());
+        }
+        match run(&mut evaluator, expression) {
+            Ok(Some(value)) => value,
+            Ok(None) => panic!("{} is not an expression", expression),
+            Err(error) => panic!("evaluating {}: {}", expression, error.message()),
+        }
+    }
+
+    fn string(value: &str) -> Value {
+        Value::LoxString(value.to_owned())
+    }
+
+    /// `error` runs `program`, which should fail with a runtime error.
+    fn error(program: &str) -> RuntimeError {
+        let mut evaluator = Evaluator::new();
+        evaluator.set_file("test.lox");
+        match run(&mut evaluator, program) {
+            Ok(_) => panic!("{} ran without error", program),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn logical_operators_give_the_operand_that_decides() {
+        assert_eq!(value("", "nil or \"a\""), string("a"));
+        assert_eq!(value("", "1 or 2"), Value::Number(1.0));
+        assert_eq!(value("", "false and 1"), Value::Boolean(false));
+        assert_eq!(value("", "true and nil"), Value::Nil);
+        // `and` binds more tightly than `or`, and both more loosely than `==`.
+        assert_eq!(value("", "true or false and false"), Value::Boolean(true));
+        assert_eq!(value("", "1 == 2 or 3 == 3"), Value::Boolean(true));
+        assert_eq!(value("", "false or true ? \"y\" : \"n\""), string("y"));
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        let program = "var calls = 0;\n\
+                       fun touch(value) { calls = calls + 1; return value; }\n\
+                       var a = true or touch(false);\n\
+                       var b = false and touch(true);\n\
+                       var c = false or touch(1);\n\
+                       var d = true and touch(2);";
+        assert_eq!(value(program, "calls"), Value::Number(2.0));
+        assert_eq!(value(program, "c"), Value::Number(1.0));
+        assert_eq!(value(program, "d"), Value::Number(2.0));
+        // The right operand is never evaluated, so its error never happens.
+        assert_eq!(value("", "true or nil + 1"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn errors_record_a_frame_per_call() {
+        let error = error(
+            "fun inner() {\n\
+             return nil + 1;\n\
+             }\n\
+             fun outer() {\n\
+             var x = 1;\n\
+             return inner();\n\
+             }\n\
+             outer();",
+        );
+        assert_eq!(
+            error.message(),
+            "Operands must be two numbers or two strings."
+        );
+        assert_eq!(
+            error.frames(),
+            [
+                Frame::new("inner", "test.lox", 2),
+                Frame::new("outer", "test.lox", 6),
+                Frame::new("script", "test.lox", 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn evaluator_errors_are_caught_as_objects() {
+        let program = "var message; var line;\n\
+                       try {\n\
+                       nil + 1;\n\
+                       } catch (e) {\n\
+                       message = e.message; line = e.line;\n\
+                       }";
+        assert_eq!(
+            value(program, "message"),
+            string("Operands must be two numbers or two strings.")
+        );
+        assert_eq!(value(program, "line"), Value::Number(3.0));
+        assert_eq!(
+            value("var x; try { throw \"thrown\"; } catch (e) { x = e; }", "x"),
+            string("thrown")
+        );
+    }
+
+    #[test]
+    fn finally_overrides_break_and_return() {
+        let program =
+            "var i = 0; while (i < 3) { i = i + 1; try { break; } finally { continue; } }";
+        assert_eq!(value(program, "i"), Value::Number(3.0));
+        let program = "fun f() { while (true) { try { return 1; } finally { break; } } return 2; }";
+        assert_eq!(value(program, "f()"), Value::Number(2.0));
+        let program = "fun f() { try { return 1; } finally { return 2; } }";
+        assert_eq!(value(program, "f()"), Value::Number(2.0));
+        let program = "fun f() { try { throw 1; } finally { return 2; } }";
+        assert_eq!(value(program, "f()"), Value::Number(2.0));
+        // A finalizer that finishes normally leaves the return alone.
+        let program = "var ran = false;\n\
+                       fun f() { try { return 1; } finally { ran = true; } }\n\
+                       var result = f();";
+        assert_eq!(value(program, "result"), Value::Number(1.0));
+        assert_eq!(value(program, "ran"), Value::Boolean(true));
+    }
+
+    #[test]
+    fn unwinding_restores_the_environment() {
+        let program = "var x = \"global\";\n\
+                       fun f() { var x = \"function\"; { var x = \"block\"; nil + 1; } }\n\
+                       try { var x = \"try\"; f(); } catch (e) {}";
+        assert_eq!(value(program, "x"), string("global"));
+        let program = "fun f() {\n\
+                       var x = \"function\";\n\
+                       try { var x = \"try\"; { var x = \"block\"; throw x; } }\n\
+                       catch (e) { return x + \" \" + e; }\n\
+                       }";
+        assert_eq!(value(program, "f()"), string("function block"));
+    }
+
+    #[test]
+    fn continue_runs_the_for_increment() {
+        let program = "var skipped = 0; var counted = 0;\n\
+                       for (var i = 0; i < 10; i = i + 1) {\n\
+                       if (i < 5) { skipped = skipped + 1; continue; }\n\
+                       counted = counted + 1;\n\
+                       }";
+        assert_eq!(value(program, "skipped"), Value::Number(5.0));
+        assert_eq!(value(program, "counted"), Value::Number(5.0));
+        // Had `continue` skipped the increment, this would never finish.
+        let program = "var n = 0; for (var i = 0; i < 3; i = i + 1) { n = n + 1; continue; }";
+        assert_eq!(value(program, "n"), Value::Number(3.0));
+    }
+
+    #[test]
+    fn lambdas_are_values() {
+        let program = "fun twice(f, x) { return f(f(x)); }\n\
+                       var inc = fun (n) { return n + 1; };\n\
+                       var passed = twice(fun (n) { return n * 3; }, 2);\n\
+                       var assigned; assigned = fun () { return \"a\"; };\n\
+                       fun adder(n) { return fun (m) { return n + m; }; }\n\
+                       var returned = adder(10)(5);\n\
+                       var called = fun (a, b) { return a - b; }(5, 3);";
+        assert_eq!(value(program, "passed"), Value::Number(18.0));
+        assert_eq!(value(program, "twice(inc, 0)"), Value::Number(2.0));
+        assert_eq!(value(program, "assigned()"), string("a"));
+        assert_eq!(value(program, "returned"), Value::Number(15.0));
+        assert_eq!(value(program, "called"), Value::Number(2.0));
+        assert_eq!(value(program, "inc").to_string(), "<fn>");
+    }
+
+    #[test]
+    fn lambdas_capture_their_enclosing_scope() {
+        let program = "fun counter() {\n\
+                       var n = 0;\n\
+                       return fun () { n = n + 1; return n; };\n\
+                       }\n\
+                       var a = counter(); var b = counter();\n\
+                       a(); a(); b();\n\
+                       var x = \"outer\"; var get;\n\
+                       { var x = \"inner\"; get = fun () { return x; }; }";
+        // Each call to `counter` makes a variable of its own, which outlives it.
+        assert_eq!(value(program, "a()"), Value::Number(3.0));
+        assert_eq!(value(program, "b()"), Value::Number(2.0));
+        assert_eq!(value(program, "get()"), string("inner"));
+        let error = error("var f = fun () { return nil + 1; };\nf();");
+        assert_eq!(error.frames()[0], Frame::new("lambda", "test.lox", 1));
+    }
+
+    /// `with_stack` runs `test` on a thread with `size` bytes of stack.
+    fn with_stack(size: usize, test: impl FnOnce() + Send + 'static) {
+        std::thread::Builder::new()
+            .stack_size(size)
+            .spawn(test)
+            .expect("the thread starts")
+            .join()
+            .expect("the test passes");
+    }
+
+    /// The stack a main thread usually has.
+    const MAIN_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+    #[test]
+    fn calls_nest_to_the_maximum_depth() {
+        with_stack(MAIN_STACK_SIZE, || {
+            let program = "fun f(n) { { { return n == 0 ? 0 : 1 + f(n - 1); } } }";
+            let depth = MAX_CALL_DEPTH - 1;
+            assert_eq!(
+                value(program, &format!("f({})", depth)),
+                Value::Number(depth as f64)
+            );
+        });
+    }
+
+    #[test]
+    fn calls_nest_deeper_with_a_raised_limit_and_a_larger_stack() {
+        with_stack(STACK_SIZE, || {
+            let mut evaluator = Evaluator::new();
+            evaluator.set_max_call_depth(DEEP_CALL_DEPTH);
+            let program = "fun f(n) { return n == 0 ? 0 : 1 + f(n - 1); }";
+            run(&mut evaluator, program).unwrap();
+            let depth = DEEP_CALL_DEPTH - 1;
+            let result = run(&mut evaluator, &format!("f({})", depth));
+            assert_eq!(result.unwrap(), Some(Value::Number(depth as f64)));
+            let error = run(&mut evaluator, &format!("f({})", depth + 1)).unwrap_err();
+            assert_eq!(error.message(), "Stack overflow.");
+        });
+    }
+
+    #[test]
+    fn runaway_recursion_overflows() {
+        with_stack(MAIN_STACK_SIZE, || {
+            let error = error(
+                "fun f() {\n\
+                               while (true) { try { f(); } finally {} }\n\
+                               }\n\
+                               f();",
+            );
+            assert_eq!(error.message(), "Stack overflow.");
+            assert_eq!(error.frames().len(), MAX_CALL_DEPTH + 1);
+        });
+    }
+
+    #[test]
+    fn lists_are_indexed_and_sliced() {
+        let program = "var xs = [1, 2, 3];\n\
+                       var ys = xs; ys[0] = 4; ys.push(5);\n\
+                       var middle = xs[1:3]; var all = xs[:]; var none = xs[4:];";
+        assert_eq!(
+            value(program, "xs[0] + xs[3] + xs.len()"),
+            Value::Number(13.0)
+        );
+        assert_eq!(value(program, "middle[0] + middle[1]"), Value::Number(5.0));
+        assert_eq!(value(program, "all.len() + none.len()"), Value::Number(4.0));
+        // A slice is a copy.
+        assert_eq!(value(program, "all == xs"), Value::Boolean(false));
+    }
+
+    #[test]
+    fn list_index_errors() {
+        for (program, message) in [
+            (
+                "[1, 2][2];",
+                "List index 2 out of range for a list of length 2.",
+            ),
+            (
+                "[1, 2][-1] = 0;",
+                "List index -1 out of range for a list of length 2.",
+            ),
+            ("[1, 2][0.5];", "List index must be an integer."),
+            ("[1, 2][\"0\"];", "List index must be an integer."),
+            (
+                "[1, 2][3:];",
+                "Slice bound 3 out of range for a list of length 2.",
+            ),
+            ("[1, 2][2:1];", "Slice start 2 is after its end 1."),
+            ("\"ab\"[0:1];", "Only lists can be sliced."),
+            ("1[0];", "Only lists and maps can be indexed."),
+            ("[].pop();", "Can't pop from an empty list."),
+            ("[].push();", "Expected 1 arguments but got 0."),
+        ] {
+            assert_eq!(error(program).message(), message, "{}", program);
+        }
+    }
+
+    #[test]
+    fn property_errors() {
+        let only = "Only lists, maps, iterators and errors have properties.";
+        for (program, message) in [
+            ("\"a\".len();", only),
+            ("var f = 1; f.x;", only),
+            ("[].size();", "Undefined property 'size'."),
+            ("range(0, 1).len();", "Undefined property 'len'."),
+        ] {
+            assert_eq!(error(program).message(), message, "{}", program);
+        }
+    }
+
+    #[test]
+    fn maps_are_looked_up_and_assigned_by_key() {
+        let program = "var m = {\"a\": 1, 2: \"two\", nil: true};\n\
+                       var n = m; n[\"b\"] = 3; m[\"a\"] = 4;";
+        assert_eq!(value(program, "m[\"a\"] + m[\"b\"]"), Value::Number(7.0));
+        assert_eq!(value(program, "m[2]"), string("two"));
+        assert_eq!(value(program, "m[nil]"), Value::Boolean(true));
+        assert_eq!(value(program, "m.len()"), Value::Number(4.0));
+    }
+
+    #[test]
+    fn map_key_errors() {
+        for (program, message) in [
+            ("{\"a\": 1}[\"b\"];", "Map has no key \"b\"."),
+            ("var x = {}[2];", "Map has no key 2."),
+            (
+                "var x = {}[[]];",
+                "A value of type list can't be used as a map key.",
+            ),
+            (
+                "var m = {}; m[{}] = 1;",
+                "A value of type map can't be used as a map key.",
+            ),
+            (
+                "var x = {[1]: 2};",
+                "A value of type list can't be used as a map key.",
+            ),
+            ("var x = {}[0 / 0];", "NaN can't be used as a map key."),
+            ("var x = {}.has();", "Expected 1 arguments but got 0."),
+        ] {
+            assert_eq!(error(program).message(), message, "{}", program);
+        }
+    }
+
+    #[test]
+    fn cyclic_lists_are_displayed() {
+        let program = "var xs = [1]; xs.push(xs); var ys = [xs, xs];";
+        assert_eq!(value(program, "xs").to_string(), "[1, [...]]");
+        assert_eq!(value(program, "ys").to_string(), "[[1, [...]], [1, [...]]]");
+    }
+
+    #[test]
+    fn cyclic_maps_are_displayed() {
+        let program = "var m = {\"a\": 1}; m[\"self\"] = m; m[\"list\"] = [m];";
+        assert_eq!(
+            value(program, "m").to_string(),
+            "{\"a\": 1, \"self\": {...}, \"list\": [{...}]}"
+        );
+    }
+
+    #[test]
+    fn for_in_iterates_built_in_values() {
+        let program = "var out = \"\";\n\
+                       fun add(x) { out = out + (out == \"\" ? \"\" : \" \") + x; }\n\
+                       for (var x in [\"a\", \"b\"]) add(x);\n\
+                       for (var k in {\"c\": 1, \"d\": 2}) add(k);\n\
+                       for (var c in \"ef\") add(c);\n\
+                       var xs = [\"g\"];\n\
+                       for (var x in xs) { if (x == \"g\") xs.push(\"h\"); add(x); }\n\
+                       var n = 0;\n\
+                       for (var i in range(0, 4)) { if (i == 1) continue; if (i == 3) break; n = n + i; }";
+        assert_eq!(value(program, "out"), string("a b c d e f g h"));
+        assert_eq!(value(program, "n"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn for_in_follows_the_iterator_protocol() {
+        let program = "fun countdown(from) {\n\
+                       return {\"iterator\": fun () {\n\
+                       var n = from;\n\
+                       return {\n\
+                       \"hasNext\": fun () { return n > 0; },\n\
+                       \"next\": fun () { n = n - 1; return n + 1; }\n\
+                       };\n\
+                       }};\n\
+                       }\n\
+                       var sum = 0; var last;\n\
+                       for (var i in countdown(4)) { sum = sum + i; last = i; }\n\
+                       var it = range(0, 2).iterator();\n\
+                       var first = it.next(); var more = it.hasNext();";
+        assert_eq!(value(program, "sum"), Value::Number(10.0));
+        assert_eq!(value(program, "last"), Value::Number(1.0));
+        assert_eq!(value(program, "first"), Value::Number(0.0));
+        assert_eq!(value(program, "more"), Value::Boolean(true));
+        let empty = error("for (var x in {\"iterator\": fun () { return {}; }}) {}");
+        assert_eq!(empty.message(), "Iterator must have a 'hasNext' method.");
+        let number = error("for (var x in 1) {}");
+        assert_eq!(
+            number.message(),
+            "Can only iterate over strings and values with an 'iterator' method."
+        );
+    }
+
+    #[test]
+    fn for_in_iterates_the_keys_of_maps_holding_data_under_protocol_names() {
+        let program = "var keys = 0; var sum = 0;\n\
+                       var m = {\"iterator\": 1, \"b\": 2};\n\
+                       for (var k in m) { keys = keys + 1; sum = sum + m[k]; }";
+        assert_eq!(value(program, "keys"), Value::Number(2.0));
+        assert_eq!(value(program, "sum"), Value::Number(3.0));
+        let call = error("var m = {\"iterator\": 1}; m[\"iterator\"]();");
+        assert_eq!(call.message(), "Can only call functions.");
+    }
+}
@@ -0,0 +1,212 @@
+use crate::scanner::Scanner;
+use crate::token::{TokenType, Trivia, TriviaKind};
+
+/// The ways highlighted source can be written out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// Text coloured with ANSI escape codes, for terminals.
+    Ansi,
+    /// HTML wrapped in a `<pre>`, with a `lox-*` class on every highlighted span.
+    Html,
+}
+
+/// The classes of source text that are highlighted differently.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Style {
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Error,
+}
+
+impl Style {
+    fn ansi(&self) -> &'static str {
+        match self {
+            Style::Keyword => "\x1b[35m",
+            Style::String => "\x1b[32m",
+            Style::Number => "\x1b[36m",
+            Style::Comment => "\x1b[90m",
+            Style::Error => "\x1b[4;31m",
+        }
+    }
+
+    fn class(&self) -> &'static str {
+        match self {
+            Style::Keyword => "lox-keyword",
+            Style::String => "lox-string",
+            Style::Number => "lox-number",
+            Style::Comment => "lox-comment",
+            Style::Error => "lox-error",
+        }
+    }
+
+    fn of_token(token_type: TokenType) -> Option<Style> {
+        match token_type {
+            TokenType::And
+            | TokenType::Break
+            | TokenType::Catch
+            | TokenType::Class
+            | TokenType::Continue
+            | TokenType::Else
+            | TokenType::False
+            | TokenType::Finally
+            | TokenType::Fun
+            | TokenType::For
+            | TokenType::If
+            | TokenType::In
+            | TokenType::Nil
+            | TokenType::Or
+            | TokenType::Print
+            | TokenType::Return
+            | TokenType::Super
+            | TokenType::This
+            | TokenType::Throw
+            | TokenType::True
+            | TokenType::Try
+            | TokenType::Var
+            | TokenType::While => Some(Style::Keyword),
+            TokenType::LoxString => Some(Style::String),
+            TokenType::Number => Some(Style::Number),
+            _ => None,
+        }
+    }
+
+    fn of_trivia(kind: TriviaKind) -> Option<Style> {
+        match kind {
+            TriviaKind::LineComment | TriviaKind::BlockComment => Some(Style::Comment),
+            TriviaKind::Error => Some(Style::Error),
+            TriviaKind::Whitespace | TriviaKind::Newline => None,
+        }
+    }
+}
+
+/// `highlight` renders `source` with keywords, strings, numbers and comments
+/// picked out. Text that fails to scan, such as an unterminated string or an
+/// unexpected character, is marked as an error rather than dropped, so the
+/// output always shows the whole of `source`.
+pub fn highlight(source: &str, format: Format) -> String {
+    let mut out = String::new();
+    if format == Format::Html {
+        out.push_str("<pre class=\"lox\">");
+    }
+    // Scan errors are skipped: their text comes back as error trivia.
+    for token in Scanner::new_lossless(source.to_owned()).flatten() {
+        push_trivia(&mut out, token.leading_trivia(), format);
+        push(
+            &mut out,
+            &token.lexeme(),
+            Style::of_token(token.token_type()),
+            format,
+        );
+        push_trivia(&mut out, token.trailing_trivia(), format);
+    }
+    if format == Format::Html {
+        out.push_str("</pre>");
+    }
+    out
+}
+
+fn push_trivia(out: &mut String, trivia: &[Trivia], format: Format) {
+    for trivia in trivia {
+        push(out, trivia.text(), Style::of_trivia(trivia.kind()), format);
+    }
+}
+
+fn push(out: &mut String, text: &str, style: Option<Style>, format: Format) {
+    match (format, style) {
+        (Format::Ansi, None) => out.push_str(text),
+        (Format::Ansi, Some(style)) => {
+            out.push_str(style.ansi());
+            out.push_str(text);
+            out.push_str("\x1b[0m");
+        }
+        (Format::Html, None) => escape_html(out, text),
+        (Format::Html, Some(style)) => {
+            out.push_str("<span class=\"");
+            out.push_str(style.class());
+            out.push_str("\">");
+            escape_html(out, text);
+            out.push_str("</span>");
+        }
+    }
+}
+
+fn escape_html(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ansi_colours_tokens_and_comments() {
+        assert_eq!(
+            highlight("var x = 1; // one\n", Format::Ansi),
+            "\x1b[35mvar\x1b[0m x = \x1b[36m1\x1b[0m; \x1b[90m// one\x1b[0m\n"
+        );
+        assert_eq!(
+            highlight("print \"hi\" and nil;", Format::Ansi),
+            "\x1b[35mprint\x1b[0m \x1b[32m\"hi\"\x1b[0m \x1b[35mand\x1b[0m \x1b[35mnil\x1b[0m;"
+        );
+    }
+
+    #[test]
+    fn ansi_underlines_what_fails_to_scan() {
+        assert_eq!(
+            highlight("print \"open", Format::Ansi),
+            "\x1b[35mprint\x1b[0m \x1b[4;31m\"open\x1b[0m"
+        );
+        assert_eq!(
+            highlight("1 @ 2", Format::Ansi),
+            "\x1b[36m1\x1b[0m \x1b[4;31m@\x1b[0m \x1b[36m2\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn html_marks_spans_with_lox_classes() {
+        assert_eq!(
+            highlight("fun f() { return 2; } /* c */", Format::Html),
+            "<pre class=\"lox\"><span class=\"lox-keyword\">fun</span> f() { \
+             <span class=\"lox-keyword\">return</span> <span class=\"lox-number\">2</span>; } \
+             <span class=\"lox-comment\">/* c */</span></pre>"
+        );
+        assert_eq!(
+            highlight("\"open", Format::Html),
+            "<pre class=\"lox\"><span class=\"lox-error\">&quot;open</span></pre>"
+        );
+    }
+
+    #[test]
+    fn html_escapes_text() {
+        assert_eq!(
+            highlight("a < b & \"<&>\" // <&\"", Format::Html),
+            "<pre class=\"lox\">a &lt; b <span class=\"lox-error\">&amp;</span> \
+             <span class=\"lox-string\">&quot;&lt;&amp;&gt;&quot;</span> \
+             <span class=\"lox-comment\">// &lt;&amp;&quot;</span></pre>"
+        );
+    }
+
+    #[test]
+    fn output_keeps_all_of_the_source() {
+        let source = "var s = \"a\";\n  /* multi\nline */ print s; // end\n@";
+        let ansi = highlight(source, Format::Ansi);
+        let mut plain = String::new();
+        let mut rest = ansi.as_str();
+        while let Some(start) = rest.find('\x1b') {
+            plain.push_str(&rest[..start]);
+            rest = &rest[start + rest[start..].find('m').unwrap() + 1..];
+        }
+        plain.push_str(rest);
+        assert_eq!(plain, source);
+    }
+}
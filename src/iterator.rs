@@ -0,0 +1,189 @@
+//! Iterators, the values `for` loops step through.
+//!
+//! A `for (var x in xs)` loop calls `xs.iterator()`, then calls `hasNext()` and
+//! `next()` on what it returns until `hasNext()` is false. Lists, maps, ranges and
+//! iterators have these methods built in, and strings are iterated by character.
+//!
+//! Rox has no classes, so Lox code takes part in the protocol with maps instead:
+//! a map with a function under `"iterator"` is iterated by calling it, and a map
+//! with functions under `"hasNext"` and `"next"` serves as an iterator. Any map
+//! holding a function under one of those keys is treated this way, so those keys
+//! are reserved for the protocol. Entries that are not functions are plain data.
+
+use crate::evaluator::{Native, Value};
+use crate::list::List;
+use crate::map::MapRef;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// A `LoxIterator` is a built-in value implementing the iterator protocol: it has
+/// `hasNext()` and `next()` methods, and an `iterator()` method returning itself so
+/// that it can be looped over directly.
+pub struct LoxIterator {
+    next: RefCell<Box<dyn FnMut() -> Option<Value>>>,
+    /// The value `has_next` took from `next` to see whether there was one.
+    peeked: RefCell<Option<Option<Value>>>,
+}
+
+impl LoxIterator {
+    pub fn new(next: impl FnMut() -> Option<Value> + 'static) -> Self {
+        LoxIterator {
+            next: RefCell::new(Box::new(next)),
+            peeked: RefCell::new(None),
+        }
+    }
+
+    pub fn has_next(&self) -> bool {
+        let mut peeked = self.peeked.borrow_mut();
+        peeked
+            .get_or_insert_with(|| (self.next.borrow_mut())())
+            .is_some()
+    }
+
+    pub fn next(&self) -> Option<Value> {
+        match self.peeked.borrow_mut().take() {
+            Some(peeked) => peeked,
+            None => (self.next.borrow_mut())(),
+        }
+    }
+}
+
+impl fmt::Debug for LoxIterator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LoxIterator")
+    }
+}
+
+impl fmt::Display for LoxIterator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<iterator>")
+    }
+}
+
+/// `list` iterates the elements of `list`, seeing any pushed while it runs.
+pub fn list(list: &List) -> LoxIterator {
+    let list = Rc::clone(list);
+    let mut index = 0;
+    LoxIterator::new(move || {
+        let element = list.borrow().get(index).cloned()?;
+        index += 1;
+        Some(element)
+    })
+}
+
+/// `keys` iterates the keys `map` has when it is called.
+pub fn keys(map: &MapRef) -> LoxIterator {
+    let keys: Vec<Value> = map.borrow().iter().map(|(key, _)| key.into()).collect();
+    let mut keys = keys.into_iter();
+    LoxIterator::new(move || keys.next())
+}
+
+/// `characters` iterates the characters of `string`, as strings of their own.
+pub fn characters(string: &str) -> LoxIterator {
+    let characters: Vec<char> = string.chars().collect();
+    let mut characters = characters.into_iter();
+    LoxIterator::new(move || characters.next().map(|c| Value::LoxString(c.to_string())))
+}
+
+/// `range` iterates the numbers from `start` up to but not including `end`.
+pub fn range(start: f64, end: f64) -> LoxIterator {
+    let mut next = start;
+    LoxIterator::new(move || {
+        if next >= end {
+            return None;
+        }
+        next += 1.0;
+        Some(Value::Number(next - 1.0))
+    })
+}
+
+/// The names of the methods `method` finds.
+pub const METHODS: &[&str] = &["hasNext", "next", "iterator"];
+
+/// `method` looks up the method `name` of `iterator`, bound to it.
+pub fn method(iterator: &Rc<LoxIterator>, name: &str) -> Option<Native> {
+    let iterator = Rc::clone(iterator);
+    let method = match name {
+        "hasNext" => Native::new(name, 0, move |_| Ok(Value::Boolean(iterator.has_next()))),
+        "next" => Native::new(name, 0, move |_| {
+            iterator
+                .next()
+                .ok_or_else(|| String::from("Iterator has no more values."))
+        }),
+        "iterator" => Native::new(name, 0, move |_| Ok(Value::Iterator(Rc::clone(&iterator)))),
+        _ => return None,
+    };
+    Some(method)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(iterator: &LoxIterator) -> Vec<Value> {
+        let mut values = vec![];
+        while let Some(value) = iterator.next() {
+            values.push(value);
+        }
+        values
+    }
+
+    fn call(iterator: &Rc<LoxIterator>, name: &str) -> Result<Value, String> {
+        let method = method(iterator, name).expect("the method exists");
+        assert_eq!(method.arity(), 0);
+        method.call(vec![])
+    }
+
+    #[test]
+    fn has_next_does_not_skip_values() {
+        let iterator = range(0.0, 2.0);
+        assert!(iterator.has_next());
+        assert!(iterator.has_next());
+        assert_eq!(iterator.next(), Some(Value::Number(0.0)));
+        assert_eq!(iterator.next(), Some(Value::Number(1.0)));
+        assert!(!iterator.has_next());
+        assert_eq!(iterator.next(), None);
+    }
+
+    #[test]
+    fn ranges_stop_before_their_end() {
+        let numbers = |start, end| collect(&range(start, end));
+        assert_eq!(
+            numbers(1.0, 4.0),
+            [1.0, 2.0, 3.0].map(Value::Number).to_vec()
+        );
+        assert_eq!(numbers(0.5, 2.0), [0.5, 1.5].map(Value::Number).to_vec());
+        assert!(numbers(3.0, 3.0).is_empty());
+        assert!(numbers(3.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn characters_are_strings() {
+        assert_eq!(
+            collect(&characters("aé")),
+            [
+                Value::LoxString(String::from("a")),
+                Value::LoxString(String::from("é"))
+            ]
+        );
+        assert!(collect(&characters("")).is_empty());
+    }
+
+    #[test]
+    fn methods_follow_the_protocol() {
+        let iterator = Rc::new(range(0.0, 1.0));
+        assert_eq!(
+            call(&iterator, "iterator"),
+            Ok(Value::Iterator(Rc::clone(&iterator)))
+        );
+        assert_eq!(call(&iterator, "hasNext"), Ok(Value::Boolean(true)));
+        assert_eq!(call(&iterator, "next"), Ok(Value::Number(0.0)));
+        assert_eq!(call(&iterator, "hasNext"), Ok(Value::Boolean(false)));
+        assert_eq!(
+            call(&iterator, "next"),
+            Err(String::from("Iterator has no more values."))
+        );
+        assert!(method(&iterator, "len").is_none());
+    }
+}
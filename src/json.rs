@@ -0,0 +1,127 @@
+//! JSON encodings of the front end's data, for tooling built outside of rox.
+//!
+//! Tokens are encoded as objects with `type`, `lexeme`, `literal` and `span` fields,
+//! plus `trivia` for tokens from a lossless scan. An AST is encoded as its root
+//! expression, or as an array of its statements if it has no root, nested: each
+//! node is an object whose `type` names the kind of node, with a `span` and one
+//! field per child or attribute.
+
+use crate::ast::Ast;
+use crate::expr::ExprTree;
+use crate::stmt::StmtTree;
+use crate::token::Token;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+pub use serde_json::Error;
+
+pub fn tokens_to_json(tokens: &[Token]) -> String {
+    serde_json::to_string_pretty(tokens).expect("tokens always serialize")
+}
+
+pub fn tokens_from_json(json: &str) -> Result<Vec<Token>, Error> {
+    from_json(json)
+}
+
+/// The encoding of a whole `Ast`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Program {
+    Expression(ExprTree),
+    Statements(Vec<StmtTree>),
+}
+
+/// `ast_to_json` encodes the tree under the root of `ast`, or its statements.
+pub fn ast_to_json(ast: &Ast) -> String {
+    let program = match ast.root() {
+        Some(root) => Program::Expression(ExprTree::from_ast(ast, root)),
+        None => Program::Statements(
+            ast.statements()
+                .iter()
+                .map(|&statement| StmtTree::from_ast(ast, statement))
+                .collect(),
+        ),
+    };
+    serde_json::to_string_pretty(&program).expect("ASTs always serialize")
+}
+
+/// `ast_from_json` builds an `Ast` from the encoding produced by `ast_to_json`.
+/// `null` is accepted as an empty program.
+pub fn ast_from_json(json: &str) -> Result<Ast, Error> {
+    let program: Option<Program> = from_json(json)?;
+    let mut ast = Ast::new();
+    match program {
+        Some(Program::Expression(tree)) => {
+            let root = tree.into_ast(&mut ast);
+            ast.set_root(root);
+        }
+        Some(Program::Statements(trees)) => {
+            for tree in trees {
+                let statement = tree.into_ast(&mut ast);
+                ast.add_statement(statement);
+            }
+        }
+        None => (),
+    }
+    Ok(ast)
+}
+
+/// `from_json` lifts serde_json's nesting limit, since an AST can nest as deeply
+/// as the parser allows.
+fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, Error> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    deserializer.disable_recursion_limit();
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astprinter::AstPrinter;
+    use crate::testing::{self, Rng};
+
+    const PROGRAM: &str = include_str!("../tests/fixtures/program.lox");
+    const PROGRAM_JSON: &str = include_str!("../tests/fixtures/program.json");
+
+    fn assert_round_trip(source: &str) {
+        let ast = testing::parse(source);
+        let decoded = ast_from_json(&ast_to_json(&ast)).expect("encoded ASTs decode");
+        assert_eq!(
+            AstPrinter::new().print(&decoded),
+            AstPrinter::new().print(&ast),
+            "{}",
+            source
+        );
+    }
+
+    #[test]
+    fn program_encodes_to_fixture() {
+        assert_eq!(
+            ast_to_json(&testing::parse(PROGRAM)),
+            PROGRAM_JSON.trim_end()
+        );
+    }
+
+    #[test]
+    fn fixture_decodes_to_program() {
+        let decoded = ast_from_json(PROGRAM_JSON).expect("the fixture decodes");
+        assert_eq!(
+            AstPrinter::new().print(&decoded),
+            AstPrinter::new().print(&testing::parse(PROGRAM))
+        );
+        // Spans are kept, not just the shape of the tree.
+        assert_eq!(ast_to_json(&decoded), PROGRAM_JSON.trim_end());
+    }
+
+    #[test]
+    fn asts_round_trip_through_json() {
+        assert_round_trip(PROGRAM);
+        assert_round_trip("");
+        let mut rng = Rng::new(37);
+        for _ in 0..500 {
+            assert_round_trip(&testing::expression(&mut rng, 6).join(" "));
+        }
+    }
+}
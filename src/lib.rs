@@ -0,0 +1,25 @@
+//! The front end of rox, an interpreter for the Lox programming language.
+
+#![allow(dead_code)]
+
+pub mod ast;
+pub mod astprinter;
+pub mod dotprinter;
+pub mod environment;
+pub mod evaluator;
+pub mod expr;
+pub mod highlighter;
+pub mod iterator;
+pub mod json;
+pub mod list;
+pub mod loxprinter;
+pub mod map;
+pub mod parser;
+pub mod repl;
+pub mod rpnprinter;
+pub mod scanner;
+pub mod stmt;
+#[cfg(test)]
+mod testing;
+pub mod token;
+pub mod visit;
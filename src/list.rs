@@ -0,0 +1,208 @@
+use crate::evaluator::{Native, Value};
+use crate::iterator;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A `List` is the value of a list literal. Lists are shared by reference, so a
+/// change made through one variable shows through every other.
+pub type List = Rc<RefCell<Vec<Value>>>;
+
+/// `index` checks that `value` can index a list of length `len`, and returns it as
+/// a `usize`.
+pub fn index(value: &Value, len: usize) -> Result<usize, String> {
+    within(value, len as f64 - 1.0, "List index", len)
+}
+
+/// `bound` checks that `value` can be an end of a slice of a list of length `len`,
+/// which unlike an index may be `len` itself.
+pub fn bound(value: &Value, len: usize) -> Result<usize, String> {
+    within(value, len as f64, "Slice bound", len)
+}
+
+/// `position` checks that `value` is where `insert` can put an element in a list
+/// of length `len`, which like a slice bound may be `len` itself.
+fn position(value: &Value, len: usize) -> Result<usize, String> {
+    within(value, len as f64, "Insert position", len)
+}
+
+/// `within` checks that `value` is an integer from 0 to `last`, naming it `what`
+/// if it is out of range.
+fn within(value: &Value, last: f64, what: &str, len: usize) -> Result<usize, String> {
+    let integer = integer(value)?;
+    if integer < 0.0 || integer > last {
+        return Err(format!(
+            "{} {} out of range for a list of length {}.",
+            what, integer, len
+        ));
+    }
+    Ok(integer as usize)
+}
+
+fn integer(value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Number(number) if number.fract() == 0.0 => Ok(*number),
+        _ => Err(String::from("List index must be an integer.")),
+    }
+}
+
+/// The names of the methods `method` finds.
+pub const METHODS: &[&str] = &["push", "pop", "len", "insert", "remove", "iterator"];
+
+/// `method` looks up the method `name` of `list`, bound to it.
+pub fn method(list: &List, name: &str) -> Option<Native> {
+    let list = Rc::clone(list);
+    let method = match name {
+        "push" => Native::new(name, 1, move |mut arguments| {
+            list.borrow_mut().push(arguments.remove(0));
+            Ok(Value::Nil)
+        }),
+        "pop" => Native::new(name, 0, move |_| {
+            list.borrow_mut()
+                .pop()
+                .ok_or_else(|| String::from("Can't pop from an empty list."))
+        }),
+        "len" => Native::new(name, 0, move |_| {
+            Ok(Value::Number(list.borrow().len() as f64))
+        }),
+        "insert" => Native::new(name, 2, move |mut arguments| {
+            let mut list = list.borrow_mut();
+            let at = position(&arguments[0], list.len())?;
+            list.insert(at, arguments.remove(1));
+            Ok(Value::Nil)
+        }),
+        "remove" => Native::new(name, 1, move |arguments| {
+            let mut list = list.borrow_mut();
+            let at = index(&arguments[0], list.len())?;
+            Ok(list.remove(at))
+        }),
+        "iterator" => Native::new(name, 0, move |_| {
+            Ok(Value::Iterator(Rc::new(iterator::list(&list))))
+        }),
+        _ => return None,
+    };
+    Some(method)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(elements: &[f64]) -> List {
+        let elements = elements.iter().map(|&n| Value::Number(n)).collect();
+        Rc::new(RefCell::new(elements))
+    }
+
+    fn call(list: &List, name: &str, arguments: Vec<Value>) -> Result<Value, String> {
+        let method = method(list, name).expect("the method exists");
+        assert_eq!(method.arity(), arguments.len());
+        method.call(arguments)
+    }
+
+    fn numbers(list: &List) -> Vec<Value> {
+        list.borrow().clone()
+    }
+
+    #[test]
+    fn indexes_must_be_integers_in_range() {
+        assert_eq!(index(&Value::Number(2.0), 3), Ok(2));
+        assert_eq!(
+            index(&Value::Number(3.0), 3),
+            Err(String::from(
+                "List index 3 out of range for a list of length 3."
+            ))
+        );
+        assert_eq!(
+            index(&Value::Number(-1.0), 3),
+            Err(String::from(
+                "List index -1 out of range for a list of length 3."
+            ))
+        );
+        let not_integer = Err(String::from("List index must be an integer."));
+        assert_eq!(index(&Value::Number(0.5), 3), not_integer);
+        assert_eq!(index(&Value::LoxString(String::from("0")), 3), not_integer);
+    }
+
+    #[test]
+    fn slice_bounds_may_be_the_length() {
+        assert_eq!(bound(&Value::Number(3.0), 3), Ok(3));
+        assert_eq!(
+            bound(&Value::Number(4.0), 3),
+            Err(String::from(
+                "Slice bound 4 out of range for a list of length 3."
+            ))
+        );
+        assert_eq!(
+            bound(&Value::Nil, 3),
+            Err(String::from("List index must be an integer."))
+        );
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let xs = list(&[1.0]);
+        assert_eq!(call(&xs, "push", vec![Value::Number(2.0)]), Ok(Value::Nil));
+        assert_eq!(call(&xs, "len", vec![]), Ok(Value::Number(2.0)));
+        assert_eq!(call(&xs, "pop", vec![]), Ok(Value::Number(2.0)));
+        assert_eq!(call(&xs, "pop", vec![]), Ok(Value::Number(1.0)));
+        assert_eq!(
+            call(&xs, "pop", vec![]),
+            Err(String::from("Can't pop from an empty list."))
+        );
+        assert_eq!(call(&xs, "len", vec![]), Ok(Value::Number(0.0)));
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let xs = list(&[1.0, 3.0]);
+        call(&xs, "insert", vec![Value::Number(1.0), Value::Number(2.0)]).unwrap();
+        call(&xs, "insert", vec![Value::Number(3.0), Value::Number(4.0)]).unwrap();
+        assert_eq!(numbers(&xs), numbers(&list(&[1.0, 2.0, 3.0, 4.0])));
+        assert_eq!(
+            call(&xs, "insert", vec![Value::Number(5.0), Value::Nil]),
+            Err(String::from(
+                "Insert position 5 out of range for a list of length 4."
+            ))
+        );
+        assert_eq!(
+            call(&xs, "insert", vec![Value::Number(-1.0), Value::Nil]),
+            Err(String::from(
+                "Insert position -1 out of range for a list of length 4."
+            ))
+        );
+        assert_eq!(
+            call(&xs, "remove", vec![Value::Number(0.0)]),
+            Ok(Value::Number(1.0))
+        );
+        assert_eq!(
+            call(&xs, "remove", vec![Value::Number(3.0)]),
+            Err(String::from(
+                "List index 3 out of range for a list of length 3."
+            ))
+        );
+        assert_eq!(
+            call(&xs, "remove", vec![Value::Boolean(true)]),
+            Err(String::from("List index must be an integer."))
+        );
+        assert_eq!(numbers(&xs), numbers(&list(&[2.0, 3.0, 4.0])));
+    }
+
+    #[test]
+    fn iterator_sees_pushed_elements() {
+        let xs = list(&[1.0]);
+        let Ok(Value::Iterator(iterator)) = call(&xs, "iterator", vec![]) else {
+            panic!("iterator() gives an iterator");
+        };
+        assert_eq!(iterator.next(), Some(Value::Number(1.0)));
+        xs.borrow_mut().push(Value::Number(2.0));
+        assert_eq!(iterator.next(), Some(Value::Number(2.0)));
+        assert_eq!(iterator.next(), None);
+    }
+
+    #[test]
+    fn unknown_methods_are_not_found() {
+        assert!(method(&list(&[]), "keys").is_none());
+        for name in METHODS {
+            assert!(method(&list(&[]), name).is_some(), "{}", name);
+        }
+    }
+}
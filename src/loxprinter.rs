@@ -0,0 +1,540 @@
+/// A `LoxPrinter` renders an AST back into Lox source.
+/// Output is canonical: operators are separated by single spaces, and parentheses
+/// appear only for `Grouping` nodes or where the shape of the tree could not be
+/// parsed back without them. Each statement goes on its own line, indented by four
+/// spaces for each block it is nested in.
+#[derive(Default)]
+pub struct LoxPrinter {
+    depth: usize,
+}
+
+use crate::ast::{Ast, ExprId, StmtId};
+use crate::expr::{Expr, ExprVisitor};
+use crate::parser::{opens_map, Parser, Precedence};
+use crate::scanner::Scanner;
+use crate::stmt::StmtVisitor;
+use crate::token::{Literal, Token, TokenType};
+
+impl LoxPrinter {
+    pub fn new() -> Self {
+        LoxPrinter { depth: 0 }
+    }
+
+    /// `print` renders the root expression of `ast`, or else its statements.
+    pub fn print(&mut self, ast: &Ast) -> String {
+        match ast.root() {
+            Some(root) => {
+                let root = self.evaluate(ast, root);
+                Self::statement_start(root)
+            }
+            None => self.statements(ast, ast.statements()),
+        }
+    }
+
+    /// `statement_start` parenthesizes `expression` if it would be read back as a
+    /// block at the start of a statement, as an empty map would be.
+    fn statement_start(expression: String) -> String {
+        let mut tokens = Scanner::new(expression.clone())
+            .skip(1)
+            .map(|token| token.map_or(TokenType::Eof, |token| token.token_type()));
+        if expression.starts_with('{') && !opens_map(|| tokens.next().unwrap_or(TokenType::Eof)) {
+            format!("({})", expression)
+        } else {
+            expression
+        }
+    }
+
+    /// `statements` renders each of `statements` on its own line at the current depth.
+    fn statements(&mut self, ast: &Ast, statements: &[StmtId]) -> String {
+        let indent = "    ".repeat(self.depth);
+        statements
+            .iter()
+            .map(|&statement| format!("{}{}", indent, self.execute(ast, statement)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// `block` renders `statements` between braces, indented one level deeper.
+    fn block(&mut self, ast: &Ast, statements: &[StmtId]) -> String {
+        if statements.is_empty() {
+            return String::from("{}");
+        }
+        self.depth += 1;
+        let body = self.statements(ast, statements);
+        self.depth -= 1;
+        format!("{{\n{}\n{}}}", body, "    ".repeat(self.depth))
+    }
+
+    /// `function` renders the parameter list and body of a function.
+    fn function(&mut self, ast: &Ast, params: &[Token], body: &[StmtId]) -> String {
+        let params: Vec<String> = params.iter().map(Token::lexeme).collect();
+        format!("({}) {}", params.join(", "), self.block(ast, body))
+    }
+
+    /// `operand` renders `id`, parenthesized if it binds more loosely than `min`.
+    fn operand(&mut self, ast: &Ast, id: ExprId, min: Precedence) -> String {
+        let expr = self.evaluate(ast, id);
+        if Self::precedence(ast, id) < min {
+            format!("({})", expr)
+        } else {
+            expr
+        }
+    }
+
+    fn precedence(ast: &Ast, id: ExprId) -> Precedence {
+        match ast.get(id) {
+            Expr::Assign(..) | Expr::SetIndex(..) => Precedence::Assignment,
+            Expr::Binary(_, operator, _) | Expr::Logical(_, operator, _) => {
+                Parser::precedence(operator)
+            }
+            Expr::Comma(..) => Precedence::Comma,
+            Expr::Conditional(..) => Precedence::Conditional,
+            Expr::Unary(..) => Precedence::Unary,
+            Expr::Call(..) | Expr::Get(..) | Expr::Index(..) | Expr::Slice(..) => Precedence::Call,
+            Expr::Grouping(..)
+            | Expr::Lambda(..)
+            | Expr::List(..)
+            | Expr::Literal(..)
+            | Expr::Map(..)
+            | Expr::Variable(..) => Precedence::Primary,
+        }
+    }
+}
+
+impl ExprVisitor<String> for LoxPrinter {
+    fn visit_assign_expr(&mut self, ast: &Ast, _id: ExprId, name: &Token, value: ExprId) -> String {
+        let value = self.operand(ast, value, Precedence::Assignment);
+        format!("{} = {}", name.lexeme(), value)
+    }
+
+    fn visit_binary_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        left: ExprId,
+        operator: &Token,
+        right: ExprId,
+    ) -> String {
+        // Binary operators are left-associative, so a right operand at the same
+        // precedence needs parentheses to keep its place in the tree.
+        let precedence = Parser::precedence(operator);
+        let left = self.operand(ast, left, precedence);
+        let right = self.operand(ast, right, precedence.next());
+        format!("{} {} {}", left, operator.lexeme(), right)
+    }
+
+    fn visit_call_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        callee: ExprId,
+        _paren: &Token,
+        arguments: &[ExprId],
+    ) -> String {
+        let callee = self.operand(ast, callee, Precedence::Call);
+        let arguments: Vec<String> = arguments
+            .iter()
+            .map(|&argument| self.operand(ast, argument, Precedence::Assignment))
+            .collect();
+        format!("{}({})", callee, arguments.join(", "))
+    }
+
+    fn visit_comma_expr(&mut self, ast: &Ast, _id: ExprId, left: ExprId, right: ExprId) -> String {
+        let left = self.operand(ast, left, Precedence::Comma);
+        let right = self.operand(ast, right, Precedence::Assignment);
+        format!("{}, {}", left, right)
+    }
+
+    fn visit_conditional_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        condition: ExprId,
+        then_branch: ExprId,
+        else_branch: ExprId,
+    ) -> String {
+        // The conditional operator is right-associative: a nested conditional is only
+        // allowed unparenthesized in the else branch.
+        let condition = self.operand(ast, condition, Precedence::Or);
+        let then_branch = self.operand(ast, then_branch, Precedence::Comma);
+        let else_branch = self.operand(ast, else_branch, Precedence::Conditional);
+        format!("{} ? {} : {}", condition, then_branch, else_branch)
+    }
+
+    fn visit_get_expr(&mut self, ast: &Ast, _id: ExprId, object: ExprId, name: &Token) -> String {
+        let object = self.operand(ast, object, Precedence::Call);
+        format!("{}.{}", object, name.lexeme())
+    }
+
+    fn visit_grouping_expr(&mut self, ast: &Ast, _id: ExprId, expression: ExprId) -> String {
+        format!("({})", self.evaluate(ast, expression))
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        object: ExprId,
+        _bracket: &Token,
+        index: ExprId,
+    ) -> String {
+        let object = self.operand(ast, object, Precedence::Call);
+        format!("{}[{}]", object, self.evaluate(ast, index))
+    }
+
+    fn visit_lambda_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        _keyword: &Token,
+        params: &[Token],
+        body: &[StmtId],
+    ) -> String {
+        format!("fun {}", self.function(ast, params, body))
+    }
+
+    fn visit_list_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        _bracket: &Token,
+        elements: &[ExprId],
+    ) -> String {
+        let elements: Vec<String> = elements
+            .iter()
+            .map(|&element| self.operand(ast, element, Precedence::Assignment))
+            .collect();
+        format!("[{}]", elements.join(", "))
+    }
+
+    fn visit_map_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        _brace: &Token,
+        entries: &[ExprId],
+    ) -> String {
+        let entries: Vec<String> = entries
+            .chunks(2)
+            .map(|entry| {
+                let key = self.operand(ast, entry[0], Precedence::Assignment);
+                let value = self.operand(ast, entry[1], Precedence::Assignment);
+                format!("{}: {}", key, value)
+            })
+            .collect();
+        format!("{{{}}}", entries.join(", "))
+    }
+
+    fn visit_literal_expr(&mut self, _ast: &Ast, _id: ExprId, value: &Literal) -> String {
+        match value {
+            Literal::LoxString(value) => format!("\"{}\"", value),
+            other => other.to_string(),
+        }
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        left: ExprId,
+        operator: &Token,
+        right: ExprId,
+    ) -> String {
+        // `and` and `or` are left-associative, like the binary operators.
+        let precedence = Parser::precedence(operator);
+        let left = self.operand(ast, left, precedence);
+        let right = self.operand(ast, right, precedence.next());
+        format!("{} {} {}", left, operator.lexeme(), right)
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        object: ExprId,
+        _bracket: &Token,
+        index: ExprId,
+        value: ExprId,
+    ) -> String {
+        let object = self.operand(ast, object, Precedence::Call);
+        let index = self.evaluate(ast, index);
+        let value = self.operand(ast, value, Precedence::Assignment);
+        format!("{}[{}] = {}", object, index, value)
+    }
+
+    fn visit_slice_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        object: ExprId,
+        _bracket: &Token,
+        start: Option<ExprId>,
+        end: Option<ExprId>,
+    ) -> String {
+        let object = self.operand(ast, object, Precedence::Call);
+        let start = start.map_or(String::new(), |start| self.evaluate(ast, start));
+        let end = end.map_or(String::new(), |end| self.evaluate(ast, end));
+        format!("{}[{}:{}]", object, start, end)
+    }
+
+    fn visit_unary_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        operator: &Token,
+        right: ExprId,
+    ) -> String {
+        let right = self.operand(ast, right, Precedence::Unary);
+        format!("{}{}", operator.lexeme(), right)
+    }
+
+    fn visit_variable_expr(&mut self, _ast: &Ast, _id: ExprId, name: &Token) -> String {
+        name.lexeme()
+    }
+}
+
+impl StmtVisitor<String> for LoxPrinter {
+    fn visit_block_stmt(&mut self, ast: &Ast, _id: StmtId, statements: &[StmtId]) -> String {
+        self.block(ast, statements)
+    }
+
+    fn visit_break_stmt(&mut self, _ast: &Ast, _id: StmtId, _keyword: &Token) -> String {
+        String::from("break;")
+    }
+
+    fn visit_continue_stmt(&mut self, _ast: &Ast, _id: StmtId, _keyword: &Token) -> String {
+        String::from("continue;")
+    }
+
+    fn visit_expression_stmt(&mut self, ast: &Ast, _id: StmtId, expression: ExprId) -> String {
+        let expression = self.evaluate(ast, expression);
+        format!("{};", Self::statement_start(expression))
+    }
+
+    fn visit_for_in_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        iterable: ExprId,
+        body: StmtId,
+    ) -> String {
+        format!(
+            "for (var {} in {}) {}",
+            name.lexeme(),
+            self.evaluate(ast, iterable),
+            self.execute(ast, body)
+        )
+    }
+
+    fn visit_function_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        params: &[Token],
+        body: &[StmtId],
+    ) -> String {
+        format!("fun {}{}", name.lexeme(), self.function(ast, params, body))
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        condition: ExprId,
+        then_branch: StmtId,
+        else_branch: Option<StmtId>,
+    ) -> String {
+        let mut statement = format!(
+            "if ({}) {}",
+            self.evaluate(ast, condition),
+            self.execute(ast, then_branch)
+        );
+        if let Some(else_branch) = else_branch {
+            statement.push_str(&format!(" else {}", self.execute(ast, else_branch)));
+        }
+        statement
+    }
+
+    fn visit_print_stmt(&mut self, ast: &Ast, _id: StmtId, expression: ExprId) -> String {
+        format!("print {};", self.evaluate(ast, expression))
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        _keyword: &Token,
+        value: Option<ExprId>,
+    ) -> String {
+        match value {
+            Some(value) => format!("return {};", self.evaluate(ast, value)),
+            None => String::from("return;"),
+        }
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        _keyword: &Token,
+        value: ExprId,
+    ) -> String {
+        format!("throw {};", self.evaluate(ast, value))
+    }
+
+    fn visit_try_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        body: StmtId,
+        name: Option<&Token>,
+        handler: Option<StmtId>,
+        finalizer: Option<StmtId>,
+    ) -> String {
+        let mut statement = format!("try {}", self.execute(ast, body));
+        if let (Some(name), Some(handler)) = (name, handler) {
+            let handler = self.execute(ast, handler);
+            statement.push_str(&format!(" catch ({}) {}", name.lexeme(), handler));
+        }
+        if let Some(finalizer) = finalizer {
+            statement.push_str(&format!(" finally {}", self.execute(ast, finalizer)));
+        }
+        statement
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        initializer: Option<ExprId>,
+    ) -> String {
+        match initializer {
+            Some(initializer) => {
+                format!(
+                    "var {} = {};",
+                    name.lexeme(),
+                    self.evaluate(ast, initializer)
+                )
+            }
+            None => format!("var {};", name.lexeme()),
+        }
+    }
+
+    /// A loop with an increment came from a `for`, and is printed as one again,
+    /// without the initializer, which stays in the enclosing block.
+    fn visit_while_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        condition: ExprId,
+        body: StmtId,
+        increment: Option<ExprId>,
+    ) -> String {
+        let condition = self.evaluate(ast, condition);
+        let body = self.execute(ast, body);
+        match increment {
+            Some(increment) => {
+                let increment = self.evaluate(ast, increment);
+                format!("for (; {}; {}) {}", condition, increment, body)
+            }
+            None => format!("while ({}) {}", condition, body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astprinter::AstPrinter;
+    use crate::testing::{self, Rng, StripGroupings};
+    use crate::visit::Fold;
+
+    /// `assert_same_tree` checks that printing `ast` and parsing the result gives
+    /// back the same tree. Trees are compared by their `AstPrinter` output, which
+    /// quotes strings, since printing moves the spans. The parser puts back a
+    /// `Grouping` wherever the printer had to add parentheses, so those are taken
+    /// out again when `ast` had none to begin with.
+    fn assert_same_tree(ast: &Ast, source: &str) {
+        let printed = LoxPrinter::new().print(ast);
+        let mut reparsed = testing::parse(&printed);
+        // Folding drops nothing but the groupings.
+        if StripGroupings.fold(ast).len() == ast.len() {
+            reparsed = StripGroupings.fold(&reparsed);
+        }
+        assert_eq!(
+            AstPrinter::new().print(&reparsed),
+            AstPrinter::new().print(ast),
+            "{} was printed as {}",
+            source,
+            printed
+        );
+    }
+
+    /// `assert_round_trip` checks that `source` prints to something that parses to
+    /// the same tree, both as parsed and with its groupings taken out, which leaves
+    /// the printer to put parentheses back where they are needed.
+    fn assert_round_trip(source: &str) {
+        let ast = testing::parse(source);
+        assert_same_tree(&ast, source);
+        assert_same_tree(&StripGroupings.fold(&ast), source);
+    }
+
+    #[test]
+    fn maps_at_the_start_of_a_statement_round_trip() {
+        for source in [
+            "{\"b\": 2};",
+            "{\"b\": 2}",
+            "{b: 2}[b] = 3;",
+            "({-1: 2}).len();",
+            "{-1: 2}.len();",
+            "({1 + 2: 3});",
+            "{\"a\" + \"b\": 1};",
+            "{a ? b : c: d};",
+            "({});",
+            "print {1 + 2: 3};",
+        ] {
+            assert_round_trip(source);
+        }
+        assert_eq!(
+            LoxPrinter::new().print(&testing::parse("{\"b\": 2};")),
+            "{\"b\": 2};"
+        );
+    }
+
+    #[test]
+    fn maps_that_would_read_as_blocks_are_parenthesized() {
+        // Parsed, these maps have a `Grouping` around them, which is taken away
+        // to leave a tree the parser could not have built from the printed source
+        // if the printer left out parentheses an empty map needs.
+        for (source, printed) in [
+            ("({})", "({})"),
+            ("({1: 2})", "{1: 2}"),
+            ("({1 + 2: 3})", "{1 + 2: 3}"),
+        ] {
+            let mut ast = testing::parse(source);
+            let root = ast.root().expect("the source is an expression");
+            let Expr::Grouping(map) = *ast.get(root) else {
+                panic!("{} is not a grouping", source);
+            };
+            ast.set_root(map);
+            assert_eq!(LoxPrinter::new().print(&ast), printed);
+            testing::parse(printed);
+        }
+    }
+
+    #[test]
+    fn printed_expressions_parse_to_the_same_tree() {
+        let mut rng = Rng::new(35);
+        for _ in 0..2000 {
+            let expression = testing::any_expression(&mut rng, 5).join(" ");
+            assert_round_trip(&format!("print {};", expression));
+            // At the start of a statement, an empty map reads as a block.
+            if !expression.starts_with("{ }") {
+                assert_round_trip(&format!("{};", expression));
+            }
+        }
+    }
+}
@@ -1,85 +1,361 @@
 #![allow(dead_code)]
 
-use clap::Parser;
+use clap::{CommandFactory, ErrorKind, Parser, Subcommand, ValueEnum};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{CompletionType, Config, Editor};
+use std::collections::HashMap;
+use std::env;
 use std::fs;
-use std::io::{self, Write};
-use std::path::Path;
+use std::io::{self, IsTerminal, Read};
+use std::mem;
+use std::path::PathBuf;
 use std::process;
+use std::rc::Rc;
+use std::thread;
 
-mod astprinter;
-mod expr;
-mod parser;
-mod scanner;
-mod token;
+use rox::ast::Ast;
+use rox::astprinter::AstPrinter;
+use rox::dotprinter::DotPrinter;
+use rox::evaluator::{self, Evaluator, RuntimeError};
+use rox::highlighter::{self, Format};
+use rox::json;
+use rox::parser::ParseError;
+use rox::parser::Parser as LoxParser;
+use rox::repl::ReplHelper;
+use rox::rpnprinter::RpnPrinter;
+use rox::scanner::{Scanner, ScannerError};
+use rox::token::{Span, Token, TokenType};
 
-use crate::astprinter::AstPrinter;
-use crate::parser::ParseError;
-use crate::parser::Parser as LoxParser;
-use crate::scanner::{Scanner, ScannerError};
-use crate::token::{Token, TokenType};
+/// The ways `parse` can write out a script.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Emit {
+    /// Lisp-style S-expressions
+    Sexpr,
+    /// Reverse Polish notation
+    Rpn,
+    /// A Graphviz DOT graph
+    Dot,
+    /// The scanned tokens as JSON, without parsing
+    TokensJson,
+    /// The AST as JSON
+    #[clap(alias = "json")]
+    AstJson,
+}
+
+/// The ways `highlight` can mark up source.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum HighlightFormat {
+    /// ANSI escape codes for a terminal
+    Ansi,
+    /// HTML with a CSS class on each highlighted span
+    Html,
+}
+
+/// What to do with a script once its source is available.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Action {
+    Run,
+    Tokens { json: bool },
+    Parse,
+    Check,
+}
 
 struct Interpreter {
     had_error: bool,
+    had_runtime_error: bool,
+    emit: Emit,
+    evaluator: Evaluator,
+    /// The name of the source being run, for error messages.
+    file: String,
+    /// Each source run so far that is kept in memory, by name. Functions outlive
+    /// the source that defined them, so an error may need an earlier one.
+    sources: HashMap<String, String>,
+    /// How many inputs the REPL has run, so each can be named apart from the others.
+    inputs: usize,
 }
 
 impl Interpreter {
-    fn new() -> Self {
-        Interpreter { had_error: false }
-    }
-    fn run_file(&mut self, path: &Path) {
-        match fs::read_to_string(path) {
-            Ok(source) => {
-                self.run(source);
-                if self.had_error {
-                    process::exit(65);
-                }
+    fn new(emit: Emit) -> Self {
+        Interpreter {
+            had_error: false,
+            had_runtime_error: false,
+            emit,
+            evaluator: deep_evaluator(),
+            file: String::new(),
+            sources: HashMap::new(),
+            inputs: 0,
+        }
+    }
+
+    /// `set_source` records what is about to be run. Sources that aren't kept in
+    /// memory are read again from `file` if an error needs to show them.
+    fn set_source(&mut self, file: &str, source: Option<&str>) {
+        self.file = file.to_owned();
+        match source {
+            Some(source) => self.sources.insert(file.to_owned(), source.to_owned()),
+            None => self.sources.remove(file),
+        };
+        self.evaluator.set_file(file);
+    }
+
+    /// `open` returns a `Scanner` reading from the file at `path`, or from stdin
+    /// if `path` is `-`.
+    fn open(path: &str) -> io::Result<Scanner> {
+        if path == "-" {
+            Ok(Scanner::from_reader(io::stdin()))
+        } else {
+            fs::File::open(path).map(Scanner::from_reader)
+        }
+    }
+
+    fn run_file(&mut self, path: &str, action: Action) {
+        self.set_source(if path == "-" { "<stdin>" } else { path }, None);
+        match Self::open(path) {
+            Ok(scanner) => self.run_program(scanner, action),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                process::exit(64);
             }
+        }
+    }
+
+    /// `highlight_file` prints the source at `path`, or on stdin if `path` is `-`,
+    /// with syntax highlighting.
+    fn highlight_file(path: &str, format: HighlightFormat) {
+        let source = if path == "-" {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source).map(|_| source)
+        } else {
+            fs::read_to_string(path)
+        };
+        let format = match format {
+            HighlightFormat::Ansi => Format::Ansi,
+            HighlightFormat::Html => Format::Html,
+        };
+        match source {
+            Ok(source) => print!("{}", highlighter::highlight(&source, format)),
             Err(e) => {
-                eprintln!("{}", e);
+                eprintln!("{}: {}", path, e);
                 process::exit(64);
             }
         }
     }
 
+    /// `run_source` runs a whole program given on the command line.
+    fn run_source(&mut self, source: String) {
+        self.set_source("<eval>", Some(&source));
+        self.run_program(Scanner::new(source), Action::Run);
+    }
+
+    fn run_program(&mut self, scanner: Scanner, action: Action) {
+        match action {
+            Action::Run => self.run(scanner),
+            Action::Parse => self.print_ast(scanner),
+            Action::Tokens { json } => self.tokens(scanner, json),
+            Action::Check => self.check(scanner),
+        }
+        if self.had_error {
+            process::exit(65);
+        }
+        if self.had_runtime_error {
+            process::exit(70);
+        }
+    }
+
+    /// `run_prompt` reads and runs input a line at a time until stdin is closed.
+    /// Input that is obviously unfinished is held back, and more is asked for
+    /// with a `...` prompt. Lines starting with `:` are meta-commands.
     fn run_prompt(&mut self) {
-        loop {
-            let mut line = String::from("");
-            print!("> ");
-            io::stdout().flush().unwrap();
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .build();
+        let mut editor: Editor<ReplHelper, DefaultHistory> = match Editor::with_config(config) {
+            Ok(editor) => editor,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(74);
+            }
+        };
+        editor.set_helper(Some(ReplHelper::new()));
+        let history = history_path();
+        if let Some(path) = &history {
+            // There is no history the first time the REPL is started.
+            let _ = editor.load_history(path);
+        }
 
-            // If user input is invalid (ex: non utf8), panic
-            io::stdin()
-                .read_line(&mut line)
-                .expect("Error reading input");
+        let mut source = String::new();
+        loop {
+            let prompt = if source.is_empty() { "> " } else { "... " };
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    source.push_str(&line);
+                    source.push('\n');
+                }
+                // Ctrl-C throws away whatever has been typed so far.
+                Err(ReadlineError::Interrupted) => {
+                    source.clear();
+                    continue;
+                }
+                Err(ReadlineError::Eof) => break,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    break;
+                }
+            }
 
-            self.run(String::from(line.trim()));
+            if source.trim().is_empty() {
+                source.clear();
+                continue;
+            }
+            if is_incomplete(&source) {
+                continue;
+            }
+            let input = mem::take(&mut source);
+            let _ = editor.add_history_entry(input.trim_end());
+            if let Some(command) = input.trim().strip_prefix(':') {
+                if !self.meta_command(command) {
+                    break;
+                }
+            } else {
+                // A function defined by an earlier input may fail during this
+                // one, so inputs are named apart to find the span in the right one.
+                self.inputs += 1;
+                self.set_source(&format!("<repl:{}>", self.inputs), Some(&input));
+                self.run(Scanner::new(input));
+            }
             self.had_error = false;
+            self.had_runtime_error = false;
+
+            if let Some(helper) = editor.helper_mut() {
+                helper.set_globals(self.evaluator.globals());
+            }
+        }
+
+        if let Some(path) = &history {
+            if let Err(e) = editor.save_history(path) {
+                eprintln!("{}: {}", path.display(), e);
+            }
         }
     }
 
-    fn run(&mut self, source: String) {
-        let mut scanner = Scanner::new(source);
+    /// `meta_command` carries out a REPL command, given without its leading `:`.
+    /// It returns `false` once the REPL should stop.
+    fn meta_command(&mut self, command: &str) -> bool {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+        match name {
+            "tokens" => self.tokens(Scanner::new(argument.to_owned()), false),
+            "ast" => self.print_ast(Scanner::new(argument.to_owned())),
+            "env" => {
+                for (name, value) in self.evaluator.globals() {
+                    println!("{} = {}", name, value);
+                }
+            }
+            "load" => match fs::read_to_string(argument) {
+                Ok(source) => {
+                    self.set_source(argument, Some(&source));
+                    self.run(Scanner::new(source));
+                }
+                Err(e) => eprintln!("{}: {}", argument, e),
+            },
+            "reset" => self.evaluator = deep_evaluator(),
+            "quit" => return false,
+            _ => eprintln!(
+                "Unknown command ':{}'. Commands are :tokens, :ast, :env, :load, :reset and :quit.",
+                name
+            ),
+        }
+        true
+    }
+
+    /// `run` runs the program, printing its value if it is a bare expression.
+    fn run(&mut self, scanner: Scanner) {
+        if let Some(ast) = self.parse(scanner) {
+            match self.evaluator.interpret(Rc::new(ast)) {
+                Ok(Some(value)) => println!("{}", value),
+                Ok(None) => (),
+                Err(e) => self.runtime_error(e),
+            }
+        }
+    }
+
+    fn print_ast(&mut self, scanner: Scanner) {
+        if self.emit == Emit::TokensJson {
+            return self.tokens(scanner, true);
+        }
+        if let Some(ast) = self.parse(scanner) {
+            println!("{}", self.emit(&ast));
+        }
+    }
 
-        match scanner.scan_tokens() {
+    /// `tokens` prints every token `scanner` produces, one per line.
+    fn tokens(&mut self, scanner: Scanner, json: bool) {
+        match scanner.collect::<Result<Vec<_>, _>>() {
+            Ok(tokens) if json => println!("{}", json::tokens_to_json(&tokens)),
             Ok(tokens) => {
-                let mut parser = LoxParser::new(tokens.to_owned());
-                let result = parser.parse();
-                if result.is_err() {
-                    match result.unwrap_err() {
-                        ParseError::UnbalancedParens(token, message)
-                        | ParseError::UnknownPrimary(token, message) => {
-                            self.parse_error(token, &message);
-                        }
-                    }
-                } else {
-                    println!("{}", AstPrinter::new().print(*(result.unwrap())));
+                for token in tokens {
+                    println!(
+                        "{:>4} {:<13} {}",
+                        token.line(),
+                        format!("{:?}", token.token_type()),
+                        token.lexeme()
+                    );
+                }
+            }
+            Err(e) => self.error(ParseError::Scan(e)),
+        }
+    }
+
+    /// `check` reports any errors in the source without printing anything else.
+    fn check(&mut self, scanner: Scanner) {
+        self.parse(scanner);
+    }
+
+    fn parse(&mut self, scanner: Scanner) -> Option<Ast> {
+        let mut parser = LoxParser::from_scanner(scanner);
+        match parser.parse() {
+            Ok(ast) => Some(ast),
+            Err(errors) => {
+                for error in errors {
+                    self.error(error);
                 }
+                None
+            }
+        }
+    }
+
+    fn emit(&self, ast: &Ast) -> String {
+        match self.emit {
+            Emit::Sexpr => AstPrinter::new().print(ast),
+            Emit::Rpn => RpnPrinter::new().print(ast),
+            Emit::Dot => DotPrinter::new().print(ast),
+            Emit::AstJson => json::ast_to_json(ast),
+            Emit::TokensJson => unreachable!("tokens are emitted before parsing"),
+        }
+    }
+
+    fn error(&mut self, error: ParseError) {
+        match error {
+            ParseError::UnbalancedParens(token, message)
+            | ParseError::UnknownPrimary(token, message)
+            | ParseError::MissingLeftOperand(token, message)
+            | ParseError::UnexpectedToken(token, message)
+            | ParseError::InvalidAssignment(token, message)
+            | ParseError::Misplaced(token, message) => {
+                self.parse_error(token, &message);
             }
-            Err(e) => match e {
+            ParseError::Scan(e) => match e {
                 ScannerError::UnterminatedString(line) => {
                     self.scan_error(line, "Unterminated String")
                 }
                 ScannerError::UnexpectedChar(line) => self.scan_error(line, "Unexpected character"),
+                ScannerError::UnterminatedComment(line) => {
+                    self.scan_error(line, "Unterminated block comment")
+                }
+                ScannerError::Io(line, e) => self.scan_error(line, &e.to_string()),
             },
         }
     }
@@ -100,25 +376,308 @@ impl Interpreter {
         }
     }
 
+    /// `runtime_error` reports `error` with the source it happened in, when that
+    /// can be found, followed by a stack trace.
+    fn runtime_error(&mut self, error: RuntimeError) {
+        eprintln!("{}\n[line {}]", error.message(), error.token().line());
+        // The span is in the source of the innermost frame, which may be a function
+        // defined in another file or an earlier REPL input.
+        let file = error
+            .frames()
+            .first()
+            .map_or(self.file.as_str(), |frame| frame.file());
+        let source = match self.sources.get(file) {
+            Some(source) => Some(source.clone()),
+            None if file != "<stdin>" => fs::read_to_string(file).ok(),
+            None => None,
+        };
+        if let Some(snippet) = source.and_then(|source| snippet(&source, error.span())) {
+            eprint!("{}", snippet);
+        }
+        // Runaway recursion would otherwise print the same frame hundreds of times.
+        for frames in error.frames().chunk_by(|a, b| a == b) {
+            let frame = &frames[0];
+            eprintln!(
+                "    at {} ({}:{})",
+                frame.function(),
+                frame.file(),
+                frame.line()
+            );
+            if frames.len() > 1 {
+                eprintln!(
+                    "    [previous frame repeated {} more times]",
+                    frames.len() - 1
+                );
+            }
+        }
+        self.had_runtime_error = true;
+    }
+
     fn report(&mut self, line: usize, location: &str, message: &str) {
         eprintln!("[line {}] Error {}: {}", line, location, message);
         self.had_error = true;
     }
 }
 
+/// `snippet` shows the line of `source` where `span` starts, with the span
+/// underlined. Spans running onto later lines are cut off at the end of the first.
+/// There is no snippet for a span that does not fit `source`.
+fn snippet(source: &str, span: Span) -> Option<String> {
+    let start = span.start();
+    if start > source.len() || !source.is_char_boundary(start) {
+        return None;
+    }
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let end = span.end().clamp(start, line_end);
+    if !source.is_char_boundary(end) {
+        return None;
+    }
+
+    let number = span.line().to_string();
+    let gutter = " ".repeat(number.len());
+    let indent = source[line_start..start].chars().count();
+    let width = source[start..end].chars().count().max(1);
+    Some(format!(
+        "{} | {}\n{} | {}{}\n",
+        number,
+        &source[line_start..line_end],
+        gutter,
+        " ".repeat(indent),
+        "^".repeat(width)
+    ))
+}
+
+/// `deep_evaluator` makes an evaluator that uses the stack `main` gives the
+/// interpreter thread to allow deep recursion.
+fn deep_evaluator() -> Evaluator {
+    let mut evaluator = Evaluator::new();
+    evaluator.set_max_call_depth(evaluator::DEEP_CALL_DEPTH);
+    evaluator
+}
+
+/// `history_path` is where the REPL keeps its history between sessions.
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".rox_history"))
+}
+
+/// `is_incomplete` reports whether `source` stops partway through a string,
+/// a block comment, or a bracketed group, so the REPL should read another line.
+fn is_incomplete(source: &str) -> bool {
+    let mut depth = 0;
+    for token in Scanner::new(source.to_owned()) {
+        match token {
+            Ok(token) => match token.token_type() {
+                TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => depth += 1,
+                TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => {
+                    depth -= 1
+                }
+                _ => (),
+            },
+            Err(ScannerError::UnterminatedString(_))
+            | Err(ScannerError::UnterminatedComment(_)) => return true,
+            Err(_) => return false,
+        }
+    }
+    depth > 0
+}
+
 #[derive(Parser)]
 #[clap(author, version, about)]
+#[clap(override_usage = "rox [OPTIONS] [SCRIPT_PATH]\n    rox [OPTIONS] <SUBCOMMAND>")]
 struct InterpreterArgs {
-    /// Path to the lox script to run
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// Path to the lox script to run, or `-` to read it from stdin
     #[clap(value_parser)]
     script_path: Option<String>,
+
+    /// Run SOURCE instead of a script
+    #[clap(
+        short,
+        long,
+        value_name = "SOURCE",
+        allow_hyphen_values = true,
+        conflicts_with = "script-path"
+    )]
+    eval: Option<String>,
+
+    /// How `parse` prints the script [default: sexpr]
+    #[clap(long, value_enum, global = true)]
+    emit: Option<Emit>,
+}
+
+/// Each command that reads a script reads stdin when given `-` as the path.
+#[derive(Subcommand)]
+enum Command {
+    /// Run a Lox script
+    Run {
+        #[clap(value_parser)]
+        path: String,
+    },
+    /// Print the tokens the scanner produces for a script
+    Tokens {
+        #[clap(value_parser)]
+        path: String,
+
+        /// Print the tokens as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Print the AST the parser produces for a script, formatted by --emit
+    Parse {
+        #[clap(value_parser)]
+        path: String,
+    },
+    /// Check a script for errors without running it
+    Check {
+        #[clap(value_parser)]
+        path: String,
+    },
+    /// Print a script with syntax highlighting
+    Highlight {
+        #[clap(value_parser)]
+        path: String,
+
+        #[clap(long, value_enum, default_value = "ansi")]
+        format: HighlightFormat,
+    },
+    /// Start an interactive prompt
+    Repl,
 }
 
 fn main() {
-    let mut interpreter = Interpreter::new();
     let cli = InterpreterArgs::parse();
-    match cli.script_path {
-        Some(path) => interpreter.run_file(Path::new(&path)),
-        None => interpreter.run_prompt(),
+    // --emit is the only option that goes with a subcommand.
+    if cli.command.is_some() && (cli.eval.is_some() || cli.script_path.is_some()) {
+        InterpreterArgs::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "A script or --eval can't be given with a subcommand",
+            )
+            .exit();
+    }
+    // Only printing a script, or the REPL's :ast command, makes use of --emit.
+    let emits = matches!(
+        cli.command,
+        Some(Command::Parse { .. } | Command::Tokens { .. } | Command::Repl)
+    );
+    if cli.emit.is_some() && !emits {
+        InterpreterArgs::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--emit can only be given with the parse, tokens or repl subcommands",
+            )
+            .exit();
+    }
+    // Tokens are only ever printed as tokens.
+    let tokens = matches!(cli.command, Some(Command::Tokens { .. }));
+    if tokens && matches!(cli.emit, Some(emit) if emit != Emit::TokensJson) {
+        InterpreterArgs::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--emit can only be given with the tokens subcommand as tokens-json",
+            )
+            .exit();
+    }
+    // Deep recursion in a script recurses as deeply in the evaluator, which needs
+    // more stack than the main thread has.
+    let interpreter = thread::Builder::new()
+        .name(String::from("interpreter"))
+        .stack_size(evaluator::STACK_SIZE)
+        .spawn(move || interpret(cli));
+    match interpreter.map(thread::JoinHandle::join) {
+        Ok(Ok(())) => (),
+        // The panic has already been reported.
+        Ok(Err(_)) => process::exit(101),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(71);
+        }
+    }
+}
+
+fn interpret(cli: InterpreterArgs) {
+    let mut interpreter = Interpreter::new(cli.emit.unwrap_or(Emit::Sexpr));
+    if let Some(source) = cli.eval {
+        interpreter.run_source(source);
+        return;
+    }
+    match (cli.command, cli.script_path) {
+        (Some(Command::Run { path }), _) | (None, Some(path)) => {
+            interpreter.run_file(&path, Action::Run)
+        }
+        (Some(Command::Tokens { path, json }), _) => {
+            let json = json || cli.emit == Some(Emit::TokensJson);
+            interpreter.run_file(&path, Action::Tokens { json })
+        }
+        (Some(Command::Parse { path }), _) => interpreter.run_file(&path, Action::Parse),
+        (Some(Command::Check { path }), _) => interpreter.run_file(&path, Action::Check),
+        (Some(Command::Highlight { path, format }), _) => {
+            Interpreter::highlight_file(&path, format)
+        }
+        (Some(Command::Repl), _) => interpreter.run_prompt(),
+        // Without a script, only prompt when someone is there to answer.
+        (None, None) if io::stdin().is_terminal() => interpreter.run_prompt(),
+        (None, None) => interpreter.run_file("-", Action::Run),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippet_underlines_the_span() {
+        let source = "var a = 1;\nprint a + nil;\n";
+        let span = Span::new(17, 24, 2);
+        assert_eq!(
+            snippet(source, span).unwrap(),
+            "2 | print a + nil;\n  |       ^^^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn snippet_skips_spans_that_do_not_fit_the_source() {
+        // A span from another source may land inside a character, or past the end.
+        let source = "print \"aéééééé\";\n";
+        assert_eq!(snippet(source, Span::new(9, 12, 1)), None);
+        assert_eq!(snippet(source, Span::new(30, 34, 1)), None);
+    }
+
+    #[test]
+    fn unfinished_input_is_incomplete() {
+        for source in [
+            "print (1 +\n",
+            "fun f() {\n",
+            "var xs = [1,\n",
+            "if (a) { print [(\n",
+            "print \"abc\n",
+            "/* a comment\n",
+        ] {
+            assert!(is_incomplete(source), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn finished_input_is_complete() {
+        for source in [
+            "print 1;\n",
+            "fun f() {\nreturn [1, (2)];\n}\n",
+            "print \"a { string\";\n",
+            "// a { comment\n",
+            "/* a ( comment */\n",
+            // A stray closing bracket can't be fixed by more input, so the error
+            // is reported straight away.
+            "print 1);\n",
+            "} {\n",
+            // Nor can any other scanning error.
+            "print (1 @\n",
+        ] {
+            assert!(!is_incomplete(source), "{:?}", source);
+        }
     }
 }
@@ -0,0 +1,311 @@
+use crate::evaluator::{Native, Value};
+use crate::iterator;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// A `Key` is a value that can be hashed, and so used as a map key. Two values
+/// make the same key exactly when they are `==`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Nil,
+    Boolean(bool),
+    /// The bits of a number, with `-0` folded into `0` since the two are equal.
+    Number(u64),
+    LoxString(String),
+}
+
+impl TryFrom<&Value> for Key {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Nil => Ok(Key::Nil),
+            Value::Boolean(value) => Ok(Key::Boolean(*value)),
+            // NaN is not equal to itself, so a NaN key could never be found again.
+            Value::Number(value) if value.is_nan() => {
+                Err(String::from("NaN can't be used as a map key."))
+            }
+            Value::Number(value) if *value == 0.0 => Ok(Key::Number(0f64.to_bits())),
+            Value::Number(value) => Ok(Key::Number(value.to_bits())),
+            Value::LoxString(value) => Ok(Key::LoxString(value.clone())),
+            value => Err(format!(
+                "A value of type {} can't be used as a map key.",
+                value.type_name()
+            )),
+        }
+    }
+}
+
+impl From<&Key> for Value {
+    fn from(key: &Key) -> Self {
+        match key {
+            Key::Nil => Value::Nil,
+            Key::Boolean(value) => Value::Boolean(*value),
+            Key::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            Key::LoxString(value) => Value::LoxString(value.clone()),
+        }
+    }
+}
+
+/// A `Map` is the value of a map literal. Its entries are kept in the order their
+/// keys were first inserted.
+#[derive(Debug, Default)]
+pub struct Map {
+    entries: Vec<(Key, Value)>,
+    /// Where each key's entry is in `entries`.
+    positions: HashMap<Key, usize>,
+}
+
+/// Maps are shared by reference, like lists.
+pub type MapRef = Rc<RefCell<Map>>;
+
+impl Map {
+    pub fn new() -> Self {
+        Map::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&Value> {
+        self.positions
+            .get(key)
+            .map(|&position| &self.entries[position].1)
+    }
+
+    /// `insert` sets the value of `key`, keeping its place if it is already present.
+    pub fn insert(&mut self, key: Key, value: Value) {
+        match self.positions.get(&key) {
+            Some(&position) => self.entries[position].1 = value,
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    /// `remove` deletes the entry for `key`, returning its value.
+    pub fn remove(&mut self, key: &Key) -> Option<Value> {
+        let position = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(position);
+        for (key, _) in &self.entries[position..] {
+            *self
+                .positions
+                .get_mut(key)
+                .expect("every entry has a position") -= 1;
+        }
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, (key, value)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", Value::from(key).repr(), value.repr())?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// The names of the methods `method` finds.
+pub const METHODS: &[&str] = &["keys", "values", "has", "delete", "len", "iterator"];
+
+/// `method` looks up the method `name` of `map`, bound to it.
+pub fn method(map: &MapRef, name: &str) -> Option<Native> {
+    let map = Rc::clone(map);
+    let method = match name {
+        "keys" => Native::new(name, 0, move |_| {
+            let keys = map
+                .borrow()
+                .iter()
+                .map(|(key, _)| Value::from(key))
+                .collect();
+            Ok(Value::List(Rc::new(RefCell::new(keys))))
+        }),
+        "values" => Native::new(name, 0, move |_| {
+            let values = map
+                .borrow()
+                .iter()
+                .map(|(_, value)| value.clone())
+                .collect();
+            Ok(Value::List(Rc::new(RefCell::new(values))))
+        }),
+        "has" => Native::new(name, 1, move |arguments| {
+            let key = Key::try_from(&arguments[0])?;
+            Ok(Value::Boolean(map.borrow().get(&key).is_some()))
+        }),
+        "delete" => Native::new(name, 1, move |arguments| {
+            let key = Key::try_from(&arguments[0])?;
+            Ok(Value::Boolean(map.borrow_mut().remove(&key).is_some()))
+        }),
+        "len" => Native::new(name, 0, move |_| {
+            Ok(Value::Number(map.borrow().len() as f64))
+        }),
+        "iterator" => Native::new(name, 0, move |_| {
+            Ok(Value::Iterator(Rc::new(iterator::keys(&map))))
+        }),
+        _ => return None,
+    };
+    Some(method)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Value {
+        Value::LoxString(value.to_owned())
+    }
+
+    /// `map` builds a map from string keys to numbers.
+    fn map(entries: &[(&str, f64)]) -> MapRef {
+        let mut map = Map::new();
+        for &(key, value) in entries {
+            map.insert(Key::LoxString(key.to_owned()), Value::Number(value));
+        }
+        Rc::new(RefCell::new(map))
+    }
+
+    fn call(map: &MapRef, name: &str, arguments: Vec<Value>) -> Result<Value, String> {
+        let method = method(map, name).expect("the method exists");
+        assert_eq!(method.arity(), arguments.len());
+        method.call(arguments)
+    }
+
+    fn list(value: Result<Value, String>) -> Vec<Value> {
+        match value {
+            Ok(Value::List(list)) => list.borrow().clone(),
+            other => panic!("{:?} is not a list", other),
+        }
+    }
+
+    #[test]
+    fn keys_are_equal_when_their_values_are() {
+        assert_eq!(
+            Key::try_from(&Value::Number(-0.0)),
+            Key::try_from(&Value::Number(0.0))
+        );
+        assert_ne!(
+            Key::try_from(&Value::Number(1.0)),
+            Key::try_from(&string("1"))
+        );
+        for value in [
+            Value::Nil,
+            Value::Boolean(true),
+            Value::Number(2.5),
+            string("a"),
+        ] {
+            let key = Key::try_from(&value).unwrap();
+            assert_eq!(Value::from(&key), value);
+        }
+    }
+
+    #[test]
+    fn some_values_cant_be_keys() {
+        assert_eq!(
+            Key::try_from(&Value::Number(f64::NAN)),
+            Err(String::from("NaN can't be used as a map key."))
+        );
+        let list = Value::List(Rc::new(RefCell::new(vec![])));
+        assert_eq!(
+            Key::try_from(&list),
+            Err(String::from(
+                "A value of type list can't be used as a map key."
+            ))
+        );
+    }
+
+    #[test]
+    fn entries_keep_their_insertion_order() {
+        let m = map(&[("b", 1.0), ("a", 2.0), ("c", 3.0)]);
+        m.borrow_mut()
+            .insert(Key::LoxString(String::from("b")), Value::Number(4.0));
+        assert_eq!(m.borrow().to_string(), "{\"b\": 4, \"a\": 2, \"c\": 3}");
+        assert_eq!(
+            m.borrow_mut().remove(&Key::LoxString(String::from("b"))),
+            Some(Value::Number(4.0))
+        );
+        assert_eq!(m.borrow_mut().remove(&Key::Nil), None);
+        // The positions of the entries after the removed one are kept up to date.
+        assert_eq!(
+            m.borrow().get(&Key::LoxString(String::from("c"))),
+            Some(&Value::Number(3.0))
+        );
+        assert_eq!(m.borrow().to_string(), "{\"a\": 2, \"c\": 3}");
+    }
+
+    #[test]
+    fn keys_and_values() {
+        let m = map(&[("b", 1.0), ("a", 2.0)]);
+        assert_eq!(list(call(&m, "keys", vec![])), [string("b"), string("a")]);
+        assert_eq!(
+            list(call(&m, "values", vec![])),
+            [Value::Number(1.0), Value::Number(2.0)]
+        );
+        assert_eq!(call(&m, "len", vec![]), Ok(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn has_and_delete() {
+        let m = map(&[("a", 1.0)]);
+        assert_eq!(call(&m, "has", vec![string("a")]), Ok(Value::Boolean(true)));
+        assert_eq!(
+            call(&m, "has", vec![string("b")]),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(
+            call(&m, "delete", vec![string("a")]),
+            Ok(Value::Boolean(true))
+        );
+        assert_eq!(
+            call(&m, "delete", vec![string("a")]),
+            Ok(Value::Boolean(false))
+        );
+        assert_eq!(call(&m, "len", vec![]), Ok(Value::Number(0.0)));
+        let unhashable = Value::List(Rc::new(RefCell::new(vec![])));
+        for name in ["has", "delete"] {
+            assert_eq!(
+                call(&m, name, vec![unhashable.clone()]),
+                Err(String::from(
+                    "A value of type list can't be used as a map key."
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn iterator_gives_the_keys() {
+        let m = map(&[("b", 1.0), ("a", 2.0)]);
+        let Ok(Value::Iterator(iterator)) = call(&m, "iterator", vec![]) else {
+            panic!("iterator() gives an iterator");
+        };
+        // Keys added after the iterator was made are not seen.
+        m.borrow_mut().insert(Key::Nil, Value::Nil);
+        assert_eq!(iterator.next(), Some(string("b")));
+        assert_eq!(iterator.next(), Some(string("a")));
+        assert_eq!(iterator.next(), None);
+    }
+
+    #[test]
+    fn unknown_methods_are_not_found() {
+        assert!(method(&map(&[]), "push").is_none());
+        for name in METHODS {
+            assert!(method(&map(&[]), name).is_some(), "{}", name);
+        }
+    }
+}
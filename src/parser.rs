@@ -1,125 +1,1032 @@
+use crate::ast::{Ast, ExprId, StmtId};
 use crate::expr::Expr;
-use crate::token::{Literal, Token, TokenType};
+use crate::scanner::{Scanner, ScannerError};
+use crate::stmt::Stmt;
+use crate::token::{Literal, Span, Token, TokenType};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::mem;
+
+/// Binding power of the operators in the expression grammar, lowest first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Precedence {
+    None,
+    Comma,
+    Assignment,
+    Conditional,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    /// The precedence one level tighter than `self`, used to parse the right operand
+    /// of a left-associative operator.
+    pub(crate) fn next(self) -> Precedence {
+        match self {
+            Precedence::None => Precedence::Comma,
+            Precedence::Comma => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Conditional,
+            Precedence::Conditional => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+/// Parses an expression that begins with the token just consumed.
+type PrefixRule = fn(&mut Parser, Token) -> Result<ExprId, ParseError>;
+/// Parses the rest of an expression whose left operand has already been parsed,
+/// given the operator token just consumed.
+type InfixRule = fn(&mut Parser, ExprId, Token) -> Result<ExprId, ParseError>;
+
+/// A `ParseRule` describes how a token is parsed at the start of an expression
+/// and after a complete left operand, along with how tightly it binds as an infix
+/// operator.
+struct ParseRule {
+    prefix: Option<PrefixRule>,
+    infix: Option<InfixRule>,
+    precedence: Precedence,
+}
+
+impl ParseRule {
+    fn new(prefix: Option<PrefixRule>, infix: Option<InfixRule>, precedence: Precedence) -> Self {
+        ParseRule {
+            prefix,
+            infix,
+            precedence,
+        }
+    }
+}
+
+lazy_static! {
+    /// Parse rules for every token that can appear in an expression. A token missing
+    /// from this table ends the expression it follows and cannot start one.
+    static ref RULES: HashMap<TokenType, ParseRule> = {
+        let mut m = HashMap::new();
+        m.insert(
+            TokenType::LeftParen,
+            ParseRule::new(Some(Parser::grouping), Some(Parser::call), Precedence::Call),
+        );
+        m.insert(
+            TokenType::Comma,
+            ParseRule::new(
+                Some(Parser::missing_left_operand),
+                Some(Parser::comma),
+                Precedence::Comma,
+            ),
+        );
+        m.insert(
+            TokenType::Equal,
+            ParseRule::new(None, Some(Parser::assignment), Precedence::Assignment),
+        );
+        m.insert(
+            TokenType::Question,
+            ParseRule::new(None, Some(Parser::conditional), Precedence::Conditional),
+        );
+        m.insert(
+            TokenType::Or,
+            ParseRule::new(
+                Some(Parser::missing_left_operand),
+                Some(Parser::logical),
+                Precedence::Or,
+            ),
+        );
+        m.insert(
+            TokenType::And,
+            ParseRule::new(
+                Some(Parser::missing_left_operand),
+                Some(Parser::logical),
+                Precedence::And,
+            ),
+        );
+        for token_type in [TokenType::BangEqual, TokenType::EqualEqual] {
+            m.insert(
+                token_type,
+                ParseRule::new(
+                    Some(Parser::missing_left_operand),
+                    Some(Parser::binary),
+                    Precedence::Equality,
+                ),
+            );
+        }
+        for token_type in [
+            TokenType::Greater,
+            TokenType::GreaterEqual,
+            TokenType::Less,
+            TokenType::LessEqual,
+        ] {
+            m.insert(
+                token_type,
+                ParseRule::new(
+                    Some(Parser::missing_left_operand),
+                    Some(Parser::binary),
+                    Precedence::Comparison,
+                ),
+            );
+        }
+        m.insert(
+            TokenType::Minus,
+            ParseRule::new(Some(Parser::unary), Some(Parser::binary), Precedence::Term),
+        );
+        m.insert(
+            TokenType::Plus,
+            ParseRule::new(
+                Some(Parser::missing_left_operand),
+                Some(Parser::binary),
+                Precedence::Term,
+            ),
+        );
+        for token_type in [TokenType::Slash, TokenType::Star] {
+            m.insert(
+                token_type,
+                ParseRule::new(
+                    Some(Parser::missing_left_operand),
+                    Some(Parser::binary),
+                    Precedence::Factor,
+                ),
+            );
+        }
+        m.insert(
+            TokenType::Bang,
+            ParseRule::new(Some(Parser::unary), None, Precedence::None),
+        );
+        m.insert(
+            TokenType::Dot,
+            ParseRule::new(None, Some(Parser::get), Precedence::Call),
+        );
+        m.insert(
+            TokenType::LeftBrace,
+            ParseRule::new(Some(Parser::map), None, Precedence::None),
+        );
+        m.insert(
+            TokenType::LeftBracket,
+            ParseRule::new(Some(Parser::list), Some(Parser::index), Precedence::Call),
+        );
+        m.insert(
+            TokenType::Identifier,
+            ParseRule::new(Some(Parser::variable), None, Precedence::None),
+        );
+        m.insert(
+            TokenType::Fun,
+            ParseRule::new(Some(Parser::lambda), None, Precedence::None),
+        );
+        for token_type in [
+            TokenType::False,
+            TokenType::True,
+            TokenType::Nil,
+            TokenType::Number,
+            TokenType::LoxString,
+        ] {
+            m.insert(
+                token_type,
+                ParseRule::new(Some(Parser::literal), None, Precedence::None),
+            );
+        }
+        m
+    };
+}
+
+/// `opens_map` decides whether a `{` opens a map rather than a block, given the
+/// types of the tokens after it in turn. The first key of a map is followed by a
+/// `:` outside any brackets, while the first statement of a block only has one as
+/// part of a conditional, after its `?`, and ends at a `;` or `}` first.
+pub(crate) fn opens_map(mut next: impl FnMut() -> TokenType) -> bool {
+    let mut depth = 0;
+    let mut conditionals = 0;
+    loop {
+        match next() {
+            TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => depth += 1,
+            TokenType::RightParen | TokenType::RightBracket if depth > 0 => depth -= 1,
+            TokenType::RightBrace if depth > 0 => depth -= 1,
+            TokenType::Question if depth == 0 => conditionals += 1,
+            TokenType::Colon if depth == 0 && conditionals > 0 => conditionals -= 1,
+            TokenType::Colon if depth == 0 => return true,
+            TokenType::Semicolon | TokenType::RightBrace if depth == 0 => return false,
+            TokenType::Eof => return false,
+            _ => (),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum ParseError {
     UnbalancedParens(Token, String),
     UnknownPrimary(Token, String),
+    MissingLeftOperand(Token, String),
+    /// A token other than the one the grammar requires next, such as a missing `;`.
+    UnexpectedToken(Token, String),
+    InvalidAssignment(Token, String),
+    /// A statement where it is not allowed, such as `break` outside a loop or
+    /// `return` outside a function.
+    Misplaced(Token, String),
+    Scan(ScannerError),
 }
 
 pub struct Parser {
     tokens: Vec<Token>,
+    scanner: Option<Scanner>,
+    scan_error: Option<ScannerError>,
+    errors: Vec<ParseError>,
+    ast: Ast,
     current: usize,
+    /// Whether the next declaration is the first in the source, and so may be a
+    /// bare expression.
+    bare: bool,
+    /// How many loops enclose the statement being parsed, within the innermost function.
+    loop_depth: usize,
+    /// How many functions enclose the statement being parsed.
+    function_depth: usize,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            scanner: None,
+            scan_error: None,
+            errors: vec![],
+            ast: Ast::new(),
+            current: 0,
+            bare: false,
+            loop_depth: 0,
+            function_depth: 0,
+        }
     }
 
-    pub fn parse(&mut self) -> Result<Box<Expr>, ParseError> {
-        self.expression()
+    /// Create a `Parser` that pulls tokens from `scanner` as it needs them.
+    pub fn from_scanner(scanner: Scanner) -> Self {
+        Parser {
+            tokens: vec![],
+            scanner: Some(scanner),
+            scan_error: None,
+            errors: vec![],
+            ast: Ast::new(),
+            current: 0,
+            bare: false,
+            loop_depth: 0,
+            function_depth: 0,
+        }
     }
 
-    fn expression(&mut self) -> Result<Box<Expr>, ParseError> {
-        self.equality()
+    /// `parse` parses a program into an `Ast` holding its statements. A source that
+    /// is a single expression with no `;` after it is a bare expression instead, and
+    /// becomes the root of the `Ast`.
+    /// Every error is collected, resynchronizing at the next statement after each
+    /// one. A scanning error is returned alone, since anything reported after it is
+    /// a side effect of the source being cut short.
+    pub fn parse(&mut self) -> Result<Ast, Vec<ParseError>> {
+        self.bare = true;
+        while !self.at_end() {
+            if let Some(statement) = self.declaration() {
+                self.ast.add_statement(statement);
+            }
+        }
+        if let Some(e) = self.scan_error.take() {
+            return Err(vec![ParseError::Scan(e)]);
+        }
+        if self.errors.is_empty() {
+            Ok(std::mem::take(&mut self.ast))
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
     }
 
-    fn equality(&mut self) -> Result<Box<Expr>, ParseError> {
-        let mut expr = self.comparison()?;
+    /// `fill` pulls tokens from the scanner until the token at `index` is available,
+    /// or the source runs out. A scanning error is recorded and replaced with an
+    /// `Eof` token so that parsing winds down; `parse` reports the scanning error in
+    /// preference to anything else.
+    fn fill(&mut self, index: usize) {
+        while index >= self.tokens.len() {
+            let next = match self.scanner.as_mut() {
+                Some(scanner) => scanner.next(),
+                None => return,
+            };
+            match next {
+                Some(Ok(token)) => self.tokens.push(token),
+                Some(Err(e)) => {
+                    let line = match &e {
+                        ScannerError::UnexpectedChar(line)
+                        | ScannerError::UnterminatedString(line)
+                        | ScannerError::UnterminatedComment(line)
+                        | ScannerError::Io(line, _) => *line,
+                    };
+                    self.scan_error = Some(e);
+                    self.scanner = None;
+                    let offset = self.tokens.last().map_or(0, |token| token.span().end());
+                    let span = Span::new(offset, offset, line);
+                    self.tokens
+                        .push(Token::new(TokenType::Eof, String::new(), None, span));
+                }
+                None => self.scanner = None,
+            }
+        }
+    }
 
-        while self.token_match(&[TokenType::BangEqual, TokenType::EqualEqual]) {
-            let operator = self.previous();
-            let right = self.comparison()?;
-            expr = Box::new(Expr::Binary(expr, operator, right));
+    /// `declaration` parses a declaration or statement. On an error, it records the
+    /// error and skips ahead to the next statement, returning `None`.
+    fn declaration(&mut self) -> Option<StmtId> {
+        // Only the first declaration may be bare, not any nested inside it.
+        let bare = mem::replace(&mut self.bare, false);
+        let result = if self.token_match(&[TokenType::Var]) {
+            self.var_declaration().map(Some)
+        } else if self.starts_function_declaration() {
+            self.advance();
+            self.function_declaration().map(Some)
+        } else if bare && !self.starts_statement() {
+            self.bare_expression()
+        } else {
+            self.statement().map(Some)
+        };
+        match result {
+            Ok(statement) => statement,
+            Err(e) => {
+                self.errors.push(e);
+                self.synchronize();
+                None
+            }
         }
-        Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Box<Expr>, ParseError> {
-        let mut expr = self.term()?;
+    fn var_declaration(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous();
+        let name = self.consume(TokenType::Identifier, String::from("Expect variable name."))?;
+        let initializer = if self.token_match(&[TokenType::Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            String::from("Expect ';' after variable declaration."),
+        )?;
+        let span = keyword.span().to(semicolon.span());
+        Ok(self.ast.add(Stmt::Var(name, initializer), span))
+    }
 
-        while self.token_match(&[
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-            TokenType::Less,
-            TokenType::LessEqual,
-        ]) {
-            let operator = self.previous();
-            let right = self.term()?;
-            expr = Box::new(Expr::Binary(expr, operator, right));
+    /// A named function declaration starts `fun name`; a `fun` followed by
+    /// anything else begins a lambda, in an expression statement.
+    fn starts_function_declaration(&mut self) -> bool {
+        self.check(TokenType::Fun) && self.peek_ahead(1).token_type() == TokenType::Identifier
+    }
+
+    /// A `{` in statement position starts a block, unless it opens a map. An empty
+    /// map reads as an empty block there, so it must be parenthesized.
+    fn starts_block(&mut self) -> bool {
+        if !self.check(TokenType::LeftBrace) {
+            return false;
         }
-        Ok(expr)
+        let mut distance = 0;
+        !opens_map(|| {
+            distance += 1;
+            self.peek_ahead(distance).token_type()
+        })
     }
 
-    fn term(&mut self) -> Result<Box<Expr>, ParseError> {
-        let mut expr = self.factor()?;
+    fn function_declaration(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous();
+        let name = self.consume(TokenType::Identifier, String::from("Expect function name."))?;
+        let (params, body, closing) = self.function("function name")?;
+        let span = keyword.span().to(closing.span());
+        Ok(self.ast.add(Stmt::Function(name, params, body), span))
+    }
 
-        while self.token_match(&[TokenType::Minus, TokenType::Plus]) {
-            let operator = self.previous();
-            let right = self.factor()?;
-            expr = Box::new(Expr::Binary(expr, operator, right));
+    /// `function` parses the parameters and body of a function or lambda, after
+    /// whatever precedes the `(`, described by `after` in error messages. It
+    /// returns the closing `}` along with them, for the span.
+    fn function(&mut self, after: &str) -> Result<(Vec<Token>, Vec<StmtId>, Token), ParseError> {
+        self.consume(TokenType::LeftParen, format!("Expect '(' after {}.", after))?;
+        let mut params = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                params.push(self.consume(
+                    TokenType::Identifier,
+                    String::from("Expect parameter name."),
+                )?);
+                if !self.token_match(&[TokenType::Comma]) {
+                    break;
+                }
+            }
         }
-        Ok(expr)
+        self.consume(
+            TokenType::RightParen,
+            String::from("Expect ')' after parameters."),
+        )?;
+        self.consume(
+            TokenType::LeftBrace,
+            String::from("Expect '{' before function body."),
+        )?;
+
+        // A loop around the function does not make `break` valid inside it.
+        let loop_depth = mem::replace(&mut self.loop_depth, 0);
+        self.function_depth += 1;
+        let body = self.block_statements();
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        let closing = self.consume(
+            TokenType::RightBrace,
+            String::from("Expect '}' after function body."),
+        )?;
+        Ok((params, body, closing))
     }
 
-    fn factor(&mut self) -> Result<Box<Expr>, ParseError> {
-        let mut expr = self.unary()?;
+    /// Whether the next token starts a statement other than an expression statement.
+    fn starts_statement(&mut self) -> bool {
+        matches!(
+            self.peek().token_type(),
+            TokenType::Break
+                | TokenType::Continue
+                | TokenType::For
+                | TokenType::If
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Throw
+                | TokenType::Try
+                | TokenType::While
+        ) || self.starts_block()
+            || self.starts_function_declaration()
+    }
 
-        while self.token_match(&[TokenType::Slash, TokenType::Star]) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            expr = Box::new(Expr::Binary(expr, operator, right));
+    fn statement(&mut self) -> Result<StmtId, ParseError> {
+        if self.token_match(&[TokenType::Break, TokenType::Continue]) {
+            self.jump_statement()
+        } else if self.token_match(&[TokenType::For]) {
+            self.for_statement()
+        } else if self.token_match(&[TokenType::If]) {
+            self.if_statement()
+        } else if self.token_match(&[TokenType::While]) {
+            self.while_statement()
+        } else if self.token_match(&[TokenType::Print]) {
+            self.print_statement()
+        } else if self.token_match(&[TokenType::Return]) {
+            self.return_statement()
+        } else if self.starts_block() {
+            self.advance();
+            self.block()
+        } else if self.token_match(&[TokenType::Throw]) {
+            self.throw_statement()
+        } else if self.token_match(&[TokenType::Try]) {
+            self.try_statement()
+        } else {
+            let expr = self.expression()?;
+            self.expression_statement(expr)
         }
-        Ok(expr)
     }
 
-    fn unary(&mut self) -> Result<Box<Expr>, ParseError> {
-        if self.token_match(&[TokenType::Bang, TokenType::Minus]) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            return Ok(Box::new(Expr::Unary(operator, right)));
+    /// `bare_expression` parses the first declaration in the source when it starts
+    /// with an expression. If the expression runs to the end of the source, it is
+    /// made the root of the `Ast`; otherwise it must be an expression statement.
+    fn bare_expression(&mut self) -> Result<Option<StmtId>, ParseError> {
+        let expr = self.expression()?;
+        if self.at_end() {
+            self.ast.set_root(expr);
+            return Ok(None);
         }
-        self.primary()
+        self.expression_statement(expr).map(Some)
     }
 
-    fn primary(&mut self) -> Result<Box<Expr>, ParseError> {
-        if self.token_match(&[TokenType::False]) {
-            return Ok(Box::new(Expr::Literal(Literal::Boolean(false))));
+    fn expression_statement(&mut self, expr: ExprId) -> Result<StmtId, ParseError> {
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            String::from("Expect ';' after expression."),
+        )?;
+        let span = self.ast.span(expr).to(semicolon.span());
+        Ok(self.ast.add(Stmt::Expression(expr), span))
+    }
+
+    /// `jump_statement` parses a `break` or `continue`. One outside a loop is
+    /// recorded as an error without abandoning the statement.
+    fn jump_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous();
+        if self.loop_depth == 0 {
+            self.errors.push(ParseError::Misplaced(
+                keyword.clone(),
+                format!("'{}' outside of loop.", keyword.lexeme()),
+            ));
         }
-        if self.token_match(&[TokenType::True]) {
-            return Ok(Box::new(Expr::Literal(Literal::Boolean(true))));
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            format!("Expect ';' after '{}'.", keyword.lexeme()),
+        )?;
+        let span = keyword.span().to(semicolon.span());
+        let statement = match keyword.token_type() {
+            TokenType::Break => Stmt::Break(keyword),
+            _ => Stmt::Continue(keyword),
+        };
+        Ok(self.ast.add(statement, span))
+    }
+
+    /// `loop_body` parses the body of a loop, in which `break` and `continue` are allowed.
+    fn loop_body(&mut self) -> Result<StmtId, ParseError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// `for_statement` desugars a `for` loop into a `while` loop, inside a block
+    /// holding the initializer if there is one. The increment stays a clause of the
+    /// `while`, rather than being appended to the body, so that `continue` still
+    /// runs it.
+    fn for_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous();
+        self.consume(
+            TokenType::LeftParen,
+            String::from("Expect '(' after 'for'."),
+        )?;
+        if self.check(TokenType::Var) && self.peek_ahead(2).token_type() == TokenType::In {
+            return self.for_in_statement(keyword);
         }
-        if self.token_match(&[TokenType::Nil]) {
-            return Ok(Box::new(Expr::Literal(Literal::Nil)));
+
+        let initializer = if self.token_match(&[TokenType::Semicolon]) {
+            None
+        } else if self.token_match(&[TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            let expr = self.expression()?;
+            Some(self.expression_statement(expr)?)
+        };
+
+        let condition = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            String::from("Expect ';' after loop condition."),
+        )?;
+
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(
+            TokenType::RightParen,
+            String::from("Expect ')' after for clauses."),
+        )?;
+
+        let body = self.loop_body()?;
+        let span = keyword.span().to(self.ast.span(body));
+        // A missing condition is always true.
+        let condition = match condition {
+            Some(condition) => condition,
+            None => self
+                .ast
+                .add(Expr::Literal(Literal::Boolean(true)), semicolon.span()),
+        };
+        let body = self.ast.add(Stmt::While(condition, body, increment), span);
+        Ok(match initializer {
+            Some(initializer) => self.ast.add(Stmt::Block(vec![initializer, body]), span),
+            None => body,
+        })
+    }
+
+    fn if_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous();
+        self.consume(TokenType::LeftParen, String::from("Expect '(' after 'if'."))?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            String::from("Expect ')' after if condition."),
+        )?;
+        let then_branch = self.statement()?;
+        let mut end = self.ast.span(then_branch);
+        let else_branch = if self.token_match(&[TokenType::Else]) {
+            let else_branch = self.statement()?;
+            end = self.ast.span(else_branch);
+            Some(else_branch)
+        } else {
+            None
+        };
+        let span = keyword.span().to(end);
+        Ok(self
+            .ast
+            .add(Stmt::If(condition, then_branch, else_branch), span))
+    }
+
+    /// `for_in_statement` parses the rest of a `for (var name in iterable)` loop,
+    /// which has its own statement since the iteration can't be written in Lox.
+    fn for_in_statement(&mut self, keyword: Token) -> Result<StmtId, ParseError> {
+        self.advance();
+        let name = self.consume(TokenType::Identifier, String::from("Expect variable name."))?;
+        self.advance();
+        let iterable = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            String::from("Expect ')' after for clauses."),
+        )?;
+        let body = self.loop_body()?;
+        let span = keyword.span().to(self.ast.span(body));
+        Ok(self.ast.add(Stmt::ForIn(name, iterable, body), span))
+    }
+
+    fn while_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous();
+        self.consume(
+            TokenType::LeftParen,
+            String::from("Expect '(' after 'while'."),
+        )?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            String::from("Expect ')' after condition."),
+        )?;
+        let body = self.loop_body()?;
+        let span = keyword.span().to(self.ast.span(body));
+        Ok(self.ast.add(Stmt::While(condition, body, None), span))
+    }
+
+    fn print_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous();
+        let value = self.expression()?;
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            String::from("Expect ';' after value."),
+        )?;
+        let span = keyword.span().to(semicolon.span());
+        Ok(self.ast.add(Stmt::Print(value), span))
+    }
+
+    fn return_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous();
+        if self.function_depth == 0 {
+            self.errors.push(ParseError::Misplaced(
+                keyword.clone(),
+                String::from("Can't return from top-level code."),
+            ));
         }
+        let value = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            String::from("Expect ';' after return value."),
+        )?;
+        let span = keyword.span().to(semicolon.span());
+        Ok(self.ast.add(Stmt::Return(keyword, value), span))
+    }
+
+    /// `block` parses the statements of a block whose `{` has just been consumed.
+    fn block(&mut self) -> Result<StmtId, ParseError> {
+        let brace = self.previous();
+        let statements = self.block_statements();
+        let closing = self.consume(
+            TokenType::RightBrace,
+            String::from("Expect '}' after block."),
+        )?;
+        let span = brace.span().to(closing.span());
+        Ok(self.ast.add(Stmt::Block(statements), span))
+    }
 
-        if self.token_match(&[TokenType::Number, TokenType::LoxString]) {
-            return Ok(Box::new(Expr::Literal(self.previous().literal())));
+    /// `block_statements` parses declarations up to the `}` that ends a block,
+    /// leaving it to be consumed.
+    fn block_statements(&mut self) -> Vec<StmtId> {
+        let mut statements = vec![];
+        while !self.check(TokenType::RightBrace) && !self.at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
         }
+        statements
+    }
 
-        if self.token_match(&[TokenType::LeftParen]) {
-            let expr = self.expression()?;
-            let consume_result = self.consume(
+    fn throw_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous();
+        let value = self.expression()?;
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            String::from("Expect ';' after thrown value."),
+        )?;
+        let span = keyword.span().to(semicolon.span());
+        Ok(self.ast.add(Stmt::Throw(keyword, value), span))
+    }
+
+    fn try_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous();
+        self.consume(
+            TokenType::LeftBrace,
+            String::from("Expect '{' after 'try'."),
+        )?;
+        let body = self.block()?;
+        let mut end = self.ast.span(body);
+
+        let (name, handler) = if self.token_match(&[TokenType::Catch]) {
+            self.consume(
+                TokenType::LeftParen,
+                String::from("Expect '(' after 'catch'."),
+            )?;
+            let name = self.consume(
+                TokenType::Identifier,
+                String::from("Expect name of caught value."),
+            )?;
+            self.consume(
                 TokenType::RightParen,
-                String::from("Expect ')' after expression."),
-            );
-            if let Err(e) = consume_result {
-                return Err(e);
+                String::from("Expect ')' after caught value."),
+            )?;
+            self.consume(
+                TokenType::LeftBrace,
+                String::from("Expect '{' before catch body."),
+            )?;
+            let handler = self.block()?;
+            end = self.ast.span(handler);
+            (Some(name), Some(handler))
+        } else {
+            (None, None)
+        };
+
+        let finalizer = if self.token_match(&[TokenType::Finally]) {
+            self.consume(
+                TokenType::LeftBrace,
+                String::from("Expect '{' after 'finally'."),
+            )?;
+            let finalizer = self.block()?;
+            end = self.ast.span(finalizer);
+            Some(finalizer)
+        } else {
+            None
+        };
+
+        if handler.is_none() && finalizer.is_none() {
+            return Err(ParseError::UnexpectedToken(
+                self.peek(),
+                String::from("Expect 'catch' or 'finally' after try block."),
+            ));
+        }
+        let span = keyword.span().to(end);
+        Ok(self
+            .ast
+            .add(Stmt::Try(body, name, handler, finalizer), span))
+    }
+
+    fn expression(&mut self) -> Result<ExprId, ParseError> {
+        self.parse_precedence(Precedence::Comma)
+    }
+
+    /// `parse_precedence` parses an expression made up of operators that bind at
+    /// least as tightly as `precedence`.
+    fn parse_precedence(&mut self, precedence: Precedence) -> Result<ExprId, ParseError> {
+        let token = self.peek();
+        let prefix = match RULES.get(&token.token_type()).and_then(|rule| rule.prefix) {
+            Some(prefix) => prefix,
+            None => {
+                return Err(ParseError::UnknownPrimary(
+                    token,
+                    String::from("Expected expression"),
+                ))
+            }
+        };
+        self.advance();
+        let mut expr = prefix(self, token)?;
+
+        while let Some(rule) = RULES.get(&self.peek().token_type()) {
+            let infix = match rule.infix {
+                Some(infix) if rule.precedence >= precedence => infix,
+                _ => break,
+            };
+            let operator = self.advance();
+            expr = infix(self, expr, operator)?;
+        }
+        Ok(expr)
+    }
+
+    fn binary(&mut self, left: ExprId, operator: Token) -> Result<ExprId, ParseError> {
+        let right = self.parse_precedence(Self::precedence(&operator).next())?;
+        let span = self.ast.span(left).to(self.ast.span(right));
+        Ok(self.ast.add(Expr::Binary(left, operator, right), span))
+    }
+
+    /// `and` and `or` parse like binary operators, but make a separate node since
+    /// they don't always evaluate their right operand.
+    fn logical(&mut self, left: ExprId, operator: Token) -> Result<ExprId, ParseError> {
+        let right = self.parse_precedence(Self::precedence(&operator).next())?;
+        let span = self.ast.span(left).to(self.ast.span(right));
+        Ok(self.ast.add(Expr::Logical(left, operator, right), span))
+    }
+
+    // Anything that takes a comma-separated list of expressions, such as call
+    // arguments, must parse each element at `Precedence::Assignment` so the comma
+    // is not swallowed by this rule.
+    fn comma(&mut self, left: ExprId, _operator: Token) -> Result<ExprId, ParseError> {
+        let right = self.parse_precedence(Precedence::Assignment)?;
+        let span = self.ast.span(left).to(self.ast.span(right));
+        Ok(self.ast.add(Expr::Comma(left, right), span))
+    }
+
+    /// Assignment is right-associative, so the value is parsed at the same precedence.
+    /// An invalid target is recorded without abandoning the expression.
+    fn assignment(&mut self, target: ExprId, equals: Token) -> Result<ExprId, ParseError> {
+        let value = self.parse_precedence(Precedence::Assignment)?;
+        let span = self.ast.span(target).to(self.ast.span(value));
+        match self.ast.get(target) {
+            Expr::Variable(name) => {
+                let name = name.clone();
+                Ok(self.ast.add(Expr::Assign(name, value), span))
+            }
+            Expr::Index(object, bracket, index) => {
+                let (object, bracket, index) = (*object, bracket.clone(), *index);
+                Ok(self
+                    .ast
+                    .add(Expr::SetIndex(object, bracket, index, value), span))
+            }
+            _ => {
+                self.errors.push(ParseError::InvalidAssignment(
+                    equals,
+                    String::from("Invalid assignment target."),
+                ));
+                Ok(value)
+            }
+        }
+    }
+
+    fn conditional(&mut self, condition: ExprId, _operator: Token) -> Result<ExprId, ParseError> {
+        let then_branch = self.expression()?;
+        self.consume(
+            TokenType::Colon,
+            String::from("Expect ':' after then branch."),
+        )?;
+        let else_branch = self.parse_precedence(Precedence::Conditional)?;
+        let span = self.ast.span(condition).to(self.ast.span(else_branch));
+        Ok(self
+            .ast
+            .add(Expr::Conditional(condition, then_branch, else_branch), span))
+    }
+
+    fn unary(&mut self, operator: Token) -> Result<ExprId, ParseError> {
+        let right = self.parse_precedence(Precedence::Unary)?;
+        let span = operator.span().to(self.ast.span(right));
+        Ok(self.ast.add(Expr::Unary(operator, right), span))
+    }
+
+    fn get(&mut self, object: ExprId, _dot: Token) -> Result<ExprId, ParseError> {
+        let name = self.consume(
+            TokenType::Identifier,
+            String::from("Expect property name after '.'."),
+        )?;
+        let span = self.ast.span(object).to(name.span());
+        Ok(self.ast.add(Expr::Get(object, name), span))
+    }
+
+    /// Arguments are parsed at `Precedence::Assignment`, so the commas between them
+    /// are not taken for comma operators.
+    fn call(&mut self, callee: ExprId, paren: Token) -> Result<ExprId, ParseError> {
+        let mut arguments = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                arguments.push(self.parse_precedence(Precedence::Assignment)?);
+                if !self.token_match(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let closing = self.consume(
+            TokenType::RightParen,
+            String::from("Expect ')' after arguments."),
+        )?;
+        let span = self.ast.span(callee).to(closing.span());
+        Ok(self.ast.add(Expr::Call(callee, paren, arguments), span))
+    }
+
+    /// Elements are parsed at `Precedence::Assignment`, like call arguments.
+    fn list(&mut self, bracket: Token) -> Result<ExprId, ParseError> {
+        let mut elements = vec![];
+        if !self.check(TokenType::RightBracket) {
+            loop {
+                elements.push(self.parse_precedence(Precedence::Assignment)?);
+                if !self.token_match(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let closing = self.consume(
+            TokenType::RightBracket,
+            String::from("Expect ']' after list elements."),
+        )?;
+        let span = bracket.span().to(closing.span());
+        Ok(self.ast.add(Expr::List(bracket, elements), span))
+    }
+
+    /// Keys and values are parsed at `Precedence::Assignment`, like list elements.
+    fn map(&mut self, brace: Token) -> Result<ExprId, ParseError> {
+        let mut entries = vec![];
+        if !self.check(TokenType::RightBrace) {
+            loop {
+                entries.push(self.parse_precedence(Precedence::Assignment)?);
+                self.consume(TokenType::Colon, String::from("Expect ':' after map key."))?;
+                entries.push(self.parse_precedence(Precedence::Assignment)?);
+                if !self.token_match(&[TokenType::Comma]) {
+                    break;
+                }
             }
-            return Ok(Box::new(Expr::Grouping(expr)));
         }
-        Err(ParseError::UnknownPrimary(
-            self.peek(),
-            String::from("Expected expression"),
-        ))
+        let closing = self.consume(
+            TokenType::RightBrace,
+            String::from("Expect '}' after map entries."),
+        )?;
+        let span = brace.span().to(closing.span());
+        Ok(self.ast.add(Expr::Map(brace, entries), span))
+    }
+
+    /// `index` parses `[index]` or a slice `[start:end]`, where either end of the
+    /// slice may be left out.
+    fn index(&mut self, object: ExprId, bracket: Token) -> Result<ExprId, ParseError> {
+        let start = if self.check(TokenType::Colon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let slice = self.token_match(&[TokenType::Colon]);
+        let end = if slice && !self.check(TokenType::RightBracket) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        let closing = self.consume(
+            TokenType::RightBracket,
+            String::from("Expect ']' after index."),
+        )?;
+        let span = self.ast.span(object).to(closing.span());
+        let expr = match (slice, start) {
+            (false, Some(index)) => Expr::Index(object, bracket, index),
+            _ => Expr::Slice(object, bracket, start, end),
+        };
+        Ok(self.ast.add(expr, span))
+    }
+
+    fn lambda(&mut self, keyword: Token) -> Result<ExprId, ParseError> {
+        let (params, body, closing) = self.function("'fun'")?;
+        let span = keyword.span().to(closing.span());
+        Ok(self.ast.add(Expr::Lambda(keyword, params, body), span))
+    }
+
+    fn variable(&mut self, name: Token) -> Result<ExprId, ParseError> {
+        let span = name.span();
+        Ok(self.ast.add(Expr::Variable(name), span))
+    }
+
+    fn literal(&mut self, token: Token) -> Result<ExprId, ParseError> {
+        let value = match token.token_type() {
+            TokenType::False => Literal::Boolean(false),
+            TokenType::True => Literal::Boolean(true),
+            TokenType::Nil => Literal::Nil,
+            _ => token.literal(),
+        };
+        Ok(self.ast.add(Expr::Literal(value), token.span()))
+    }
+
+    fn grouping(&mut self, token: Token) -> Result<ExprId, ParseError> {
+        let expr = self.expression()?;
+        if !self.check(TokenType::RightParen) {
+            return Err(ParseError::UnbalancedParens(
+                self.peek(),
+                String::from("Expect ')' after expression."),
+            ));
+        }
+        let closing = self.advance();
+        let span = token.span().to(closing.span());
+        Ok(self.ast.add(Expr::Grouping(expr), span))
+    }
+
+    /// Error production for a binary operator that appears without a left operand.
+    /// The error is recorded, and the right operand is parsed at the operator's
+    /// precedence and handed back in place of the whole binary expression so that
+    /// parsing can continue.
+    fn missing_left_operand(&mut self, operator: Token) -> Result<ExprId, ParseError> {
+        let precedence = Self::precedence(&operator);
+        self.errors.push(ParseError::MissingLeftOperand(
+            operator.clone(),
+            format!("Missing left-hand operand for '{}'", operator.lexeme()),
+        ));
+        self.parse_precedence(precedence.next())
+    }
+
+    /// The precedence `token` binds with as an infix operator.
+    pub(crate) fn precedence(token: &Token) -> Precedence {
+        RULES
+            .get(&token.token_type())
+            .map_or(Precedence::None, |rule| rule.precedence)
     }
 
     fn consume(&mut self, token_type: TokenType, message: String) -> Result<Token, ParseError> {
         if self.check(token_type) {
             return Ok(self.advance());
         }
-        Err(ParseError::UnbalancedParens(self.peek(), message))
+        Err(ParseError::UnexpectedToken(self.peek(), message))
     }
 
     fn token_match(&mut self, token_types: &[TokenType]) -> bool {
@@ -132,7 +1039,7 @@ impl Parser {
         false
     }
 
-    fn check(&self, token_type: TokenType) -> bool {
+    fn check(&mut self, token_type: TokenType) -> bool {
         if self.at_end() {
             false
         } else {
@@ -147,14 +1054,25 @@ impl Parser {
         self.previous()
     }
 
-    fn at_end(&self) -> bool {
+    fn at_end(&mut self) -> bool {
         self.peek().token_type() == TokenType::Eof
     }
 
-    fn peek(&self) -> Token {
+    fn peek(&mut self) -> Token {
+        self.fill(self.current);
         self.tokens[self.current].clone()
     }
 
+    /// `peek_ahead` looks `distance` tokens past `peek`, giving the final `Eof` if
+    /// the source ends first.
+    fn peek_ahead(&mut self, distance: usize) -> Token {
+        self.fill(self.current + distance);
+        match self.tokens.get(self.current + distance) {
+            Some(token) => token.clone(),
+            None => self.tokens[self.tokens.len() - 1].clone(),
+        }
+    }
+
     fn previous(&self) -> Token {
         self.tokens[self.current - 1].clone()
     }
@@ -175,9 +1093,272 @@ impl Parser {
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => self.peek(),
+                | TokenType::Return
+                | TokenType::Throw
+                | TokenType::Try
+                | TokenType::Break
+                | TokenType::Continue => return,
                 _ => self.advance(),
             };
         }
     }
 }
+
+#[cfg(test)]
+mod recursive_descent;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astprinter::AstPrinter;
+    use crate::testing::{self, Rng};
+
+    /// `describe` renders errors with the token each points at, for comparison.
+    fn describe(errors: Vec<ParseError>) -> Vec<String> {
+        errors
+            .into_iter()
+            .map(|error| match error {
+                ParseError::UnbalancedParens(token, message)
+                | ParseError::UnknownPrimary(token, message)
+                | ParseError::MissingLeftOperand(token, message)
+                | ParseError::UnexpectedToken(token, message)
+                | ParseError::InvalidAssignment(token, message)
+                | ParseError::Misplaced(token, message) => {
+                    format!(
+                        "[line {}] at '{}': {}",
+                        token.line(),
+                        token.lexeme(),
+                        message
+                    )
+                }
+                ParseError::Scan(e) => format!("{:?}", e),
+            })
+            .collect()
+    }
+
+    /// `table_driven` parses `source` as a single expression, the way the
+    /// recursive-descent parser does.
+    fn table_driven(source: &str) -> Result<String, Vec<String>> {
+        let mut parser = Parser::from_scanner(Scanner::new(source.to_owned()));
+        let result = parser.expression();
+        if let Some(e) = parser.scan_error.take() {
+            return Err(describe(vec![ParseError::Scan(e)]));
+        }
+        let mut errors = mem::take(&mut parser.errors);
+        match result {
+            Ok(expr) if errors.is_empty() => {
+                parser.ast.set_root(expr);
+                Ok(AstPrinter::new().print(&parser.ast))
+            }
+            Ok(_) => Err(describe(errors)),
+            Err(e) => {
+                errors.push(e);
+                Err(describe(errors))
+            }
+        }
+    }
+
+    fn recursive_descent(source: &str) -> Result<String, Vec<String>> {
+        recursive_descent::Parser::from_scanner(Scanner::new(source.to_owned()))
+            .parse()
+            .map(|ast| AstPrinter::new().print(&ast))
+            .map_err(describe)
+    }
+
+    /// Whether `tokens` stay within the grammar both parsers share. A `(` right
+    /// after an operand starts a call, which the recursive-descent parser predates.
+    fn shared_grammar(tokens: &[String]) -> bool {
+        tokens.windows(2).all(|pair| {
+            let keyword = pair[0] == "and" || pair[0] == "or";
+            let operand = pair[0] == ")"
+                || !keyword && pair[0].starts_with(|c: char| c.is_alphanumeric() || c == '"');
+            !(operand && pair[1] == "(")
+        })
+    }
+
+    fn assert_same(tokens: &[String]) {
+        let source = tokens.join(" ");
+        assert_eq!(
+            table_driven(&source),
+            recursive_descent(&source),
+            "parsing {}",
+            source
+        );
+    }
+
+    #[test]
+    fn parsers_agree_on_valid_expressions() {
+        let mut rng = Rng::new(30);
+        for _ in 0..2000 {
+            let tokens = testing::expression(&mut rng, 6);
+            let source = tokens.join(" ");
+            assert!(recursive_descent(&source).is_ok(), "parsing {}", source);
+            assert_same(&tokens);
+        }
+    }
+
+    #[test]
+    fn parsers_agree_on_errors() {
+        let extra = [
+            "(", ")", "?", ":", ",", "+", "-", "*", "==", "<", "!", "and", "or", "1", "@",
+        ];
+        let mut rng = Rng::new(31);
+        for _ in 0..2000 {
+            let mut tokens = testing::expression(&mut rng, 4);
+            let position = rng.below(tokens.len() + 1);
+            if rng.below(2) == 0 && position < tokens.len() {
+                tokens.remove(position);
+            } else {
+                tokens.insert(position, rng.pick(&extra).to_string());
+            }
+            if shared_grammar(&tokens) {
+                assert_same(&tokens);
+            }
+        }
+    }
+
+    #[test]
+    fn parsers_agree_on_examples() {
+        for source in [
+            "",
+            "1 +",
+            "+ 1",
+            "* 2 == , 3",
+            "(1, 2",
+            "1 ? 2",
+            "1 ? 2 : 3 ? 4 : 5",
+            "1 == 2 == 3 < 4 - -5",
+            "1 or 2 and 3 or 4 ? 5 : 6",
+            "and 1 or",
+            "\"unterminated",
+        ] {
+            let tokens: Vec<String> = source.split(' ').map(str::to_owned).collect();
+            assert_same(&tokens);
+        }
+    }
+
+    /// `program` parses `source` as a whole program.
+    fn program(source: &str) -> Result<String, Vec<String>> {
+        Parser::from_scanner(Scanner::new(source.to_owned()))
+            .parse()
+            .map(|ast| AstPrinter::new().print(&ast))
+            .map_err(describe)
+    }
+
+    /// `first_statement` parses `source` as a program and returns its first
+    /// statement.
+    fn first_statement(source: &str) -> Stmt {
+        let ast = testing::parse(source);
+        ast.get(ast.statements()[0]).clone()
+    }
+
+    #[test]
+    fn statement_braces_open_maps_when_a_key_is_followed_by_a_colon() {
+        for source in [
+            "{-1: 2};",
+            "{\"a\" + \"b\": 1};",
+            "{(1): 2};",
+            "{a ? b : c: d};",
+            "{[1, 2][0]: {3: 4}};",
+        ] {
+            let statement = first_statement(source);
+            assert!(
+                matches!(statement, Stmt::Expression(_)),
+                "parsing {}",
+                source
+            );
+        }
+        for source in [
+            "{}",
+            "{ print 1; }",
+            "{ print a ? b : c; }",
+            "{ var m = {1: 2}; }",
+            "{ { print 1; } }",
+        ] {
+            let statement = first_statement(source);
+            assert!(matches!(statement, Stmt::Block(_)), "parsing {}", source);
+        }
+    }
+
+    #[test]
+    fn missing_left_operands_are_reported_and_parsing_goes_on() {
+        assert_eq!(
+            program("* 3;\nprint 1 +;"),
+            Err(vec![
+                String::from("[line 1] at '*': Missing left-hand operand for '*'"),
+                String::from("[line 2] at ';': Expected expression"),
+            ])
+        );
+        // The right operand is parsed and thrown away, so nothing in it is taken
+        // for another error.
+        assert_eq!(
+            program("== 4 + 5;\n< (6);\nprint 7;"),
+            Err(vec![
+                String::from("[line 1] at '==': Missing left-hand operand for '=='"),
+                String::from("[line 2] at '<': Missing left-hand operand for '<'"),
+            ])
+        );
+    }
+
+    #[test]
+    fn function_declarations_and_lambdas_are_told_apart() {
+        assert_eq!(program("fun f() {}"), Ok(String::from("(fun f ())")));
+        assert!(matches!(first_statement("fun f() {}"), Stmt::Function(..)));
+        for (source, printed) in [
+            ("fun () {}();", "(; (call (fun ())))"),
+            ("fun (a) { return a; };", "(; (fun (a) (return a)))"),
+        ] {
+            assert_eq!(program(source), Ok(String::from(printed)));
+            assert!(matches!(first_statement(source), Stmt::Expression(_)));
+        }
+        assert_eq!(
+            program("var f = fun (a, b) { return a; };"),
+            Ok(String::from("(var f (fun (a b) (return a)))"))
+        );
+        // Alone, a lambda is a bare expression; in a program it needs a `;`.
+        assert_eq!(program("fun () {}"), Ok(String::from("(fun ())")));
+        assert_eq!(
+            program("fun () {} print 1;"),
+            Err(vec![String::from(
+                "[line 1] at 'print': Expect ';' after expression."
+            )])
+        );
+    }
+
+    #[test]
+    fn jumps_must_be_inside_a_loop() {
+        for source in [
+            "while (true) { if (true) break; else continue; }",
+            "for (;;) { { break; } }",
+            "for (var x in xs) continue;",
+            "while (true) { var f = fun () { while (true) break; }; break; }",
+        ] {
+            assert!(program(source).is_ok(), "parsing {}", source);
+        }
+        for (source, errors) in [
+            (
+                "break;",
+                vec!["[line 1] at 'break': 'break' outside of loop."],
+            ),
+            (
+                "continue; print 1;",
+                vec!["[line 1] at 'continue': 'continue' outside of loop."],
+            ),
+            // A function body is outside any loop around the function.
+            (
+                "while (true) { fun () { break; }; }",
+                vec!["[line 1] at 'break': 'break' outside of loop."],
+            ),
+            (
+                "for (;;) {\nfun f() { continue; }\nbreak;\n}\ncontinue;",
+                vec![
+                    "[line 2] at 'continue': 'continue' outside of loop.",
+                    "[line 5] at 'continue': 'continue' outside of loop.",
+                ],
+            ),
+        ] {
+            let errors: Vec<String> = errors.into_iter().map(String::from).collect();
+            assert_eq!(program(source), Err(errors), "parsing {}", source);
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! The recursive-descent expression parser that the table-driven `Parser`
+//! replaced, with one function per precedence level. It is kept to check the
+//! table-driven parser against: both must build the same tree and report the
+//! same errors for the expression grammar they share.
+
+use crate::ast::{Ast, ExprId};
+use crate::expr::Expr;
+use crate::parser::ParseError;
+use crate::scanner::{Scanner, ScannerError};
+use crate::token::{Literal, Span, Token, TokenType};
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    scanner: Option<Scanner>,
+    scan_error: Option<ScannerError>,
+    errors: Vec<ParseError>,
+    ast: Ast,
+    current: usize,
+}
+
+impl Parser {
+    /// Create a `Parser` that pulls tokens from `scanner` as it needs them.
+    pub fn from_scanner(scanner: Scanner) -> Self {
+        Parser {
+            tokens: vec![],
+            scanner: Some(scanner),
+            scan_error: None,
+            errors: vec![],
+            ast: Ast::new(),
+            current: 0,
+        }
+    }
+
+    /// `parse` parses a single expression into the root of an `Ast`.
+    /// Errors that the parser can recover from are collected and returned together
+    /// with the error that stopped parsing, if any. A scanning error is returned alone,
+    /// since anything reported after it is a side effect of the source being cut short.
+    pub fn parse(&mut self) -> Result<Ast, Vec<ParseError>> {
+        let result = self.expression();
+        if let Some(e) = self.scan_error.take() {
+            return Err(vec![ParseError::Scan(e)]);
+        }
+        match result {
+            Ok(expr) if self.errors.is_empty() => {
+                self.ast.set_root(expr);
+                Ok(std::mem::take(&mut self.ast))
+            }
+            Ok(_) => Err(std::mem::take(&mut self.errors)),
+            Err(e) => {
+                self.errors.push(e);
+                Err(std::mem::take(&mut self.errors))
+            }
+        }
+    }
+
+    /// `fill` pulls tokens from the scanner until the token at `current` is available.
+    /// A scanning error is recorded and replaced with an `Eof` token so that parsing
+    /// winds down; `parse` reports the scanning error in preference to anything else.
+    fn fill(&mut self) {
+        while self.current >= self.tokens.len() {
+            let next = match self.scanner.as_mut() {
+                Some(scanner) => scanner.next(),
+                None => return,
+            };
+            match next {
+                Some(Ok(token)) => self.tokens.push(token),
+                Some(Err(e)) => {
+                    let line = match &e {
+                        ScannerError::UnexpectedChar(line)
+                        | ScannerError::UnterminatedString(line)
+                        | ScannerError::UnterminatedComment(line)
+                        | ScannerError::Io(line, _) => *line,
+                    };
+                    self.scan_error = Some(e);
+                    self.scanner = None;
+                    let offset = self.tokens.last().map_or(0, |token| token.span().end());
+                    let span = Span::new(offset, offset, line);
+                    self.tokens
+                        .push(Token::new(TokenType::Eof, String::new(), None, span));
+                }
+                None => self.scanner = None,
+            }
+        }
+    }
+
+    fn expression(&mut self) -> Result<ExprId, ParseError> {
+        self.comma()
+    }
+
+    fn comma(&mut self) -> Result<ExprId, ParseError> {
+        let mut expr = self.conditional()?;
+
+        while self.token_match(&[TokenType::Comma]) {
+            let right = self.conditional()?;
+            let span = self.ast.span(expr).to(self.ast.span(right));
+            expr = self.ast.add(Expr::Comma(expr, right), span);
+        }
+        Ok(expr)
+    }
+
+    fn conditional(&mut self) -> Result<ExprId, ParseError> {
+        let expr = self.or()?;
+
+        if self.token_match(&[TokenType::Question]) {
+            let then_branch = self.expression()?;
+            self.consume(
+                TokenType::Colon,
+                String::from("Expect ':' after then branch."),
+            )?;
+            let else_branch = self.conditional()?;
+            let span = self.ast.span(expr).to(self.ast.span(else_branch));
+            return Ok(self
+                .ast
+                .add(Expr::Conditional(expr, then_branch, else_branch), span));
+        }
+        Ok(expr)
+    }
+
+    /// `binary` parses a left-associative run of the `operators`, with operands
+    /// parsed by `operand`.
+    fn binary(
+        &mut self,
+        operators: &[TokenType],
+        operand: fn(&mut Self) -> Result<ExprId, ParseError>,
+    ) -> Result<ExprId, ParseError> {
+        let mut expr = operand(self)?;
+
+        while self.token_match(operators) {
+            let operator = self.previous();
+            let right = operand(self)?;
+            let span = self.ast.span(expr).to(self.ast.span(right));
+            expr = self.ast.add(Expr::Binary(expr, operator, right), span);
+        }
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<ExprId, ParseError> {
+        self.logical(TokenType::Or, Self::and)
+    }
+
+    fn and(&mut self) -> Result<ExprId, ParseError> {
+        self.logical(TokenType::And, Self::equality)
+    }
+
+    /// `logical` parses a left-associative run of `operator`, which is `and` or
+    /// `or`, with operands parsed by `operand`.
+    fn logical(
+        &mut self,
+        operator: TokenType,
+        operand: fn(&mut Self) -> Result<ExprId, ParseError>,
+    ) -> Result<ExprId, ParseError> {
+        let mut expr = operand(self)?;
+
+        while self.token_match(&[operator]) {
+            let operator = self.previous();
+            let right = operand(self)?;
+            let span = self.ast.span(expr).to(self.ast.span(right));
+            expr = self.ast.add(Expr::Logical(expr, operator, right), span);
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<ExprId, ParseError> {
+        self.binary(
+            &[TokenType::BangEqual, TokenType::EqualEqual],
+            Self::comparison,
+        )
+    }
+
+    fn comparison(&mut self) -> Result<ExprId, ParseError> {
+        self.binary(
+            &[
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::Less,
+                TokenType::LessEqual,
+            ],
+            Self::term,
+        )
+    }
+
+    fn term(&mut self) -> Result<ExprId, ParseError> {
+        self.binary(&[TokenType::Minus, TokenType::Plus], Self::factor)
+    }
+
+    fn factor(&mut self) -> Result<ExprId, ParseError> {
+        self.binary(&[TokenType::Slash, TokenType::Star], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<ExprId, ParseError> {
+        if self.token_match(&[TokenType::Bang, TokenType::Minus]) {
+            let operator = self.previous();
+            let right = self.unary()?;
+            let span = operator.span().to(self.ast.span(right));
+            return Ok(self.ast.add(Expr::Unary(operator, right), span));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<ExprId, ParseError> {
+        if self.token_match(&[
+            TokenType::False,
+            TokenType::True,
+            TokenType::Nil,
+            TokenType::Number,
+            TokenType::LoxString,
+        ]) {
+            let token = self.previous();
+            let literal = match token.token_type() {
+                TokenType::False => Literal::Boolean(false),
+                TokenType::True => Literal::Boolean(true),
+                TokenType::Nil => Literal::Nil,
+                _ => token.literal(),
+            };
+            return Ok(self.ast.add(Expr::Literal(literal), token.span()));
+        }
+
+        if self.token_match(&[TokenType::LeftParen]) {
+            let paren = self.previous();
+            let expr = self.expression()?;
+            let closing = self.consume(
+                TokenType::RightParen,
+                String::from("Expect ')' after expression."),
+            )?;
+            let span = paren.span().to(closing.span());
+            return Ok(self.ast.add(Expr::Grouping(expr), span));
+        }
+
+        if let Some(right) = self.missing_left_operand()? {
+            return Ok(right);
+        }
+        Err(ParseError::UnknownPrimary(
+            self.peek(),
+            String::from("Expected expression"),
+        ))
+    }
+
+    /// Error productions for a binary operator that appears without a left operand.
+    /// The error is recorded, and the right operand is parsed at the operator's
+    /// precedence and handed back in place of the whole binary expression so that
+    /// parsing can continue.
+    fn missing_left_operand(&mut self) -> Result<Option<ExprId>, ParseError> {
+        let operator = self.peek();
+        let operand: fn(&mut Self) -> Result<ExprId, ParseError> = match operator.token_type() {
+            TokenType::Comma => Self::conditional,
+            TokenType::Or => Self::and,
+            TokenType::And => Self::equality,
+            TokenType::BangEqual | TokenType::EqualEqual => Self::comparison,
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => Self::term,
+            TokenType::Plus => Self::factor,
+            TokenType::Slash | TokenType::Star => Self::unary,
+            _ => return Ok(None),
+        };
+        self.advance();
+        self.errors.push(ParseError::MissingLeftOperand(
+            operator.clone(),
+            format!("Missing left-hand operand for '{}'", operator.lexeme()),
+        ));
+        operand(self).map(Some)
+    }
+
+    fn consume(&mut self, token_type: TokenType, message: String) -> Result<Token, ParseError> {
+        if self.check(token_type) {
+            return Ok(self.advance());
+        }
+        Err(ParseError::UnbalancedParens(self.peek(), message))
+    }
+
+    fn token_match(&mut self, token_types: &[TokenType]) -> bool {
+        for token_type in token_types {
+            if self.check(*token_type) {
+                self.advance();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn check(&mut self, token_type: TokenType) -> bool {
+        if self.at_end() {
+            false
+        } else {
+            self.peek().token_type() == token_type
+        }
+    }
+
+    fn advance(&mut self) -> Token {
+        if !self.at_end() {
+            self.current += 1;
+        }
+        self.previous()
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.peek().token_type() == TokenType::Eof
+    }
+
+    fn peek(&mut self) -> Token {
+        self.fill();
+        self.tokens[self.current].clone()
+    }
+
+    fn previous(&self) -> Token {
+        self.tokens[self.current - 1].clone()
+    }
+}
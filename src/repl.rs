@@ -0,0 +1,161 @@
+use crate::evaluator::{self, Value};
+use crate::highlighter::{highlight, Format};
+use crate::scanner::KEYWORDS;
+use crate::{iterator, list, map};
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+
+/// A `ReplHelper` gives the line editor syntax highlighting, and tab completion
+/// for keywords, for the globals defined so far in the session, and for the
+/// built-in properties of values.
+#[derive(Default)]
+pub struct ReplHelper {
+    /// Each global's name, with the names of its value's properties.
+    globals: Vec<(String, &'static [&'static str])>,
+}
+
+impl ReplHelper {
+    pub fn new() -> Self {
+        ReplHelper::default()
+    }
+
+    /// `set_globals` replaces the names offered alongside keywords, and the values
+    /// whose properties are offered after a `.`. The REPL calls it after each
+    /// input, since any input may define new globals or assign to them.
+    pub fn set_globals(&mut self, globals: Vec<(String, Value)>) {
+        self.globals = globals
+            .into_iter()
+            .map(|(name, value)| (name, value.properties()))
+            .collect();
+    }
+
+    /// `completions` finds where the word ending at `pos` begins, and what it
+    /// could be completed to.
+    fn completions(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let start = word_start(line, pos);
+        let prefix = &line[start..pos];
+        let Some(object_end) = line[..start].strip_suffix('.').map(str::len) else {
+            let names = KEYWORDS
+                .keys()
+                .map(String::as_str)
+                .chain(self.globals.iter().map(|(name, _)| name.as_str()));
+            return (start, candidates(names, prefix));
+        };
+        // A name after `.` is a property. When the value before the `.` is a
+        // global its properties are known; otherwise the value is not known until
+        // the line runs, so every built-in property is offered.
+        let object = &line[word_start(line, object_end)..object_end];
+        if object.starts_with(|c: char| c.is_ascii_digit()) {
+            // The `.` is a decimal point.
+            return (start, vec![]);
+        }
+        let properties: Vec<&str> = match self.globals.iter().find(|(name, _)| name == object) {
+            Some((_, properties)) => properties.to_vec(),
+            None => [
+                list::METHODS,
+                map::METHODS,
+                iterator::METHODS,
+                evaluator::ERROR_PROPERTIES,
+            ]
+            .concat(),
+        };
+        (start, candidates(properties.into_iter(), prefix))
+    }
+}
+
+/// `candidates` lists every name starting with `prefix`, sorted and without duplicates.
+fn candidates<'a>(names: impl Iterator<Item = &'a str>, prefix: &str) -> Vec<Pair> {
+    let mut names: Vec<&str> = names.filter(|name| name.starts_with(prefix)).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| Pair {
+            display: name.to_owned(),
+            replacement: name.to_owned(),
+        })
+        .collect()
+}
+
+/// `word_start` finds where the identifier ending at `pos` begins.
+fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .map_or(0, |i| i + 1)
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(self.completions(line, pos))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight(line, Format::Ansi))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        true
+    }
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn complete(helper: &ReplHelper, line: &str) -> Vec<String> {
+        let (start, pairs) = helper.completions(line, line.len());
+        assert_eq!(start, word_start(line, line.len()));
+        pairs.into_iter().map(|pair| pair.replacement).collect()
+    }
+
+    #[test]
+    fn completes_keywords_and_globals() {
+        let mut helper = ReplHelper::new();
+        helper.set_globals(vec![(String::from("value"), Value::Nil)]);
+        assert_eq!(complete(&helper, "print va"), ["value", "var"]);
+    }
+
+    #[test]
+    fn completes_properties_of_globals() {
+        let mut helper = ReplHelper::new();
+        let list = Value::List(Rc::new(RefCell::new(vec![])));
+        helper.set_globals(vec![
+            (String::from("xs"), list),
+            (String::from("n"), Value::Number(1.0)),
+        ]);
+        assert_eq!(complete(&helper, "xs.p"), ["pop", "push"]);
+        assert_eq!(complete(&helper, "print xs.i"), ["insert", "iterator"]);
+        assert!(complete(&helper, "n.").is_empty());
+    }
+
+    #[test]
+    fn completes_every_property_of_unknown_values() {
+        let helper = ReplHelper::new();
+        assert_eq!(complete(&helper, "f().h"), ["has", "hasNext"]);
+        assert_eq!(complete(&helper, "e.l"), ["len", "line"]);
+        assert!(complete(&helper, "1.").is_empty());
+    }
+}
@@ -0,0 +1,400 @@
+/// An `RpnPrinter` renders an AST in reverse Polish notation, with every operator
+/// following its operands: `(1 + 2) * (4 - 3)` becomes `1 2 + 4 3 - *`.
+/// Groupings disappear, since RPN needs no parentheses. Unary minus is written
+/// `neg` to tell it apart from subtraction, and the conditional operator `?:`
+/// takes the condition and both branches.
+/// Statements are written one per line in the same style, with the keyword last:
+/// `print 1 + 2;` becomes `1 2 + print`. Blocks are bracketed by `{` and `}`.
+/// A call is written `call/n`, where `n` is the number of arguments, and a function
+/// as its parenthesized parameters and its body before `fun`. A list literal is
+/// written `list/n` after its elements, and a slice `[:]` after the object and both
+/// ends, with `_` for a missing end.
+#[derive(Default)]
+pub struct RpnPrinter {}
+
+use crate::ast::{Ast, ExprId, StmtId};
+use crate::expr::{walk_expr, ExprVisitor};
+use crate::stmt::StmtVisitor;
+use crate::token::{Literal, Token, TokenType};
+
+impl RpnPrinter {
+    pub fn new() -> Self {
+        RpnPrinter {}
+    }
+
+    /// `print` renders the root expression of `ast`, or else its statements.
+    pub fn print(&mut self, ast: &Ast) -> String {
+        match ast.root() {
+            Some(root) => self.evaluate(ast, root),
+            None => self.statements(ast, ast.statements()).join("\n"),
+        }
+    }
+
+    fn statements(&mut self, ast: &Ast, statements: &[StmtId]) -> Vec<String> {
+        statements
+            .iter()
+            .map(|&statement| self.execute(ast, statement))
+            .collect()
+    }
+
+    /// `function` prints the parameters and body of a function, ready for the
+    /// keyword and name that follow them.
+    fn function(&mut self, ast: &Ast, params: &[Token], body: &[StmtId]) -> String {
+        let params: Vec<String> = params.iter().map(Token::lexeme).collect();
+        let mut parts = vec![format!("({})", params.join(" ")), String::from("{")];
+        parts.extend(self.statements(ast, body));
+        parts.push(String::from("}"));
+        parts.join(" ")
+    }
+
+    /// `postfix` prints the children of `id` followed by `operator`.
+    fn postfix(&mut self, ast: &Ast, id: ExprId, operator: &str) -> String {
+        let mut parts = walk_expr(self, ast, id);
+        parts.push(operator.to_owned());
+        parts.join(" ")
+    }
+}
+
+impl ExprVisitor<String> for RpnPrinter {
+    fn visit_assign_expr(&mut self, ast: &Ast, id: ExprId, name: &Token, _value: ExprId) -> String {
+        self.postfix(ast, id, &format!("{} =", name.lexeme()))
+    }
+
+    fn visit_binary_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _left: ExprId,
+        operator: &Token,
+        _right: ExprId,
+    ) -> String {
+        self.postfix(ast, id, &operator.lexeme())
+    }
+
+    fn visit_call_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _callee: ExprId,
+        _paren: &Token,
+        arguments: &[ExprId],
+    ) -> String {
+        self.postfix(ast, id, &format!("call/{}", arguments.len()))
+    }
+
+    fn visit_comma_expr(&mut self, ast: &Ast, id: ExprId, _left: ExprId, _right: ExprId) -> String {
+        self.postfix(ast, id, ",")
+    }
+
+    fn visit_conditional_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _condition: ExprId,
+        _then_branch: ExprId,
+        _else_branch: ExprId,
+    ) -> String {
+        self.postfix(ast, id, "?:")
+    }
+
+    fn visit_get_expr(&mut self, ast: &Ast, id: ExprId, _object: ExprId, name: &Token) -> String {
+        self.postfix(ast, id, &format!("{} .", name.lexeme()))
+    }
+
+    fn visit_grouping_expr(&mut self, ast: &Ast, _id: ExprId, expression: ExprId) -> String {
+        self.evaluate(ast, expression)
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _object: ExprId,
+        _bracket: &Token,
+        _index: ExprId,
+    ) -> String {
+        self.postfix(ast, id, "[]")
+    }
+
+    fn visit_lambda_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        _keyword: &Token,
+        params: &[Token],
+        body: &[StmtId],
+    ) -> String {
+        format!("{} fun", self.function(ast, params, body))
+    }
+
+    fn visit_list_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _bracket: &Token,
+        elements: &[ExprId],
+    ) -> String {
+        self.postfix(ast, id, &format!("list/{}", elements.len()))
+    }
+
+    fn visit_map_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _brace: &Token,
+        entries: &[ExprId],
+    ) -> String {
+        self.postfix(ast, id, &format!("map/{}", entries.len() / 2))
+    }
+
+    fn visit_literal_expr(&mut self, _ast: &Ast, _id: ExprId, value: &Literal) -> String {
+        match value {
+            Literal::LoxString(value) => format!("\"{}\"", value),
+            other => other.to_string(),
+        }
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _left: ExprId,
+        operator: &Token,
+        _right: ExprId,
+    ) -> String {
+        self.postfix(ast, id, &operator.lexeme())
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        _object: ExprId,
+        _bracket: &Token,
+        _index: ExprId,
+        _value: ExprId,
+    ) -> String {
+        self.postfix(ast, id, "[]=")
+    }
+
+    fn visit_slice_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        object: ExprId,
+        _bracket: &Token,
+        start: Option<ExprId>,
+        end: Option<ExprId>,
+    ) -> String {
+        let mut bound = |bound: Option<ExprId>| match bound {
+            Some(bound) => self.evaluate(ast, bound),
+            None => String::from("_"),
+        };
+        let (start, end) = (bound(start), bound(end));
+        format!("{} {} {} [:]", self.evaluate(ast, object), start, end)
+    }
+
+    fn visit_unary_expr(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        operator: &Token,
+        _right: ExprId,
+    ) -> String {
+        match operator.token_type() {
+            TokenType::Minus => self.postfix(ast, id, "neg"),
+            _ => self.postfix(ast, id, &operator.lexeme()),
+        }
+    }
+
+    fn visit_variable_expr(&mut self, _ast: &Ast, _id: ExprId, name: &Token) -> String {
+        name.lexeme()
+    }
+}
+
+impl StmtVisitor<String> for RpnPrinter {
+    fn visit_block_stmt(&mut self, ast: &Ast, _id: StmtId, statements: &[StmtId]) -> String {
+        let mut parts = vec![String::from("{")];
+        parts.extend(self.statements(ast, statements));
+        parts.push(String::from("}"));
+        parts.join(" ")
+    }
+
+    fn visit_break_stmt(&mut self, _ast: &Ast, _id: StmtId, _keyword: &Token) -> String {
+        String::from("break")
+    }
+
+    fn visit_continue_stmt(&mut self, _ast: &Ast, _id: StmtId, _keyword: &Token) -> String {
+        String::from("continue")
+    }
+
+    fn visit_expression_stmt(&mut self, ast: &Ast, _id: StmtId, expression: ExprId) -> String {
+        format!("{} ;", self.evaluate(ast, expression))
+    }
+
+    fn visit_for_in_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        iterable: ExprId,
+        body: StmtId,
+    ) -> String {
+        format!(
+            "{} {} {} for",
+            self.evaluate(ast, iterable),
+            name.lexeme(),
+            self.execute(ast, body)
+        )
+    }
+
+    fn visit_function_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        params: &[Token],
+        body: &[StmtId],
+    ) -> String {
+        format!("{} {} fun", self.function(ast, params, body), name.lexeme())
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        condition: ExprId,
+        then_branch: StmtId,
+        else_branch: Option<StmtId>,
+    ) -> String {
+        let mut parts = vec![
+            self.evaluate(ast, condition),
+            self.execute(ast, then_branch),
+        ];
+        if let Some(else_branch) = else_branch {
+            parts.push(self.execute(ast, else_branch));
+            parts.push(String::from("else"));
+        }
+        parts.push(String::from("if"));
+        parts.join(" ")
+    }
+
+    fn visit_print_stmt(&mut self, ast: &Ast, _id: StmtId, expression: ExprId) -> String {
+        format!("{} print", self.evaluate(ast, expression))
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        _keyword: &Token,
+        value: Option<ExprId>,
+    ) -> String {
+        match value {
+            Some(value) => format!("{} return", self.evaluate(ast, value)),
+            None => String::from("return"),
+        }
+    }
+
+    fn visit_throw_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        _keyword: &Token,
+        value: ExprId,
+    ) -> String {
+        format!("{} throw", self.evaluate(ast, value))
+    }
+
+    fn visit_try_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        body: StmtId,
+        name: Option<&Token>,
+        handler: Option<StmtId>,
+        finalizer: Option<StmtId>,
+    ) -> String {
+        let mut parts = vec![self.execute(ast, body)];
+        if let (Some(name), Some(handler)) = (name, handler) {
+            parts.push(name.lexeme());
+            parts.push(self.execute(ast, handler));
+            parts.push(String::from("catch"));
+        }
+        if let Some(finalizer) = finalizer {
+            parts.push(self.execute(ast, finalizer));
+            parts.push(String::from("finally"));
+        }
+        parts.push(String::from("try"));
+        parts.join(" ")
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        name: &Token,
+        initializer: Option<ExprId>,
+    ) -> String {
+        match initializer {
+            Some(initializer) => {
+                format!("{} {} var", self.evaluate(ast, initializer), name.lexeme())
+            }
+            None => format!("{} var", name.lexeme()),
+        }
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        condition: ExprId,
+        body: StmtId,
+        increment: Option<ExprId>,
+    ) -> String {
+        let mut parts = vec![self.evaluate(ast, condition), self.execute(ast, body)];
+        if let Some(increment) = increment {
+            parts.push(self.evaluate(ast, increment));
+            parts.push(String::from("then"));
+        }
+        parts.push(String::from("while"));
+        parts.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn rpn(source: &str) -> String {
+        RpnPrinter::new().print(&testing::parse(source))
+    }
+
+    #[test]
+    fn operators_follow_their_operands() {
+        assert_eq!(rpn("(1 + 2) * (4 - 3)"), "1 2 + 4 3 - *");
+        assert_eq!(rpn("1 - 2 - 3"), "1 2 - 3 -");
+        assert_eq!(rpn("1 - (2 - 3)"), "1 2 3 - -");
+        assert_eq!(rpn("-x - !y"), "x neg y ! -");
+        assert_eq!(rpn("a or b and c"), "a b c and or");
+        assert_eq!(rpn("a ? b : c ? d : e"), "a b c d e ?: ?:");
+        assert_eq!(rpn("a = b, \"s\""), "b a = \"s\" ,");
+    }
+
+    #[test]
+    fn calls_and_collections() {
+        assert_eq!(rpn("f(1, g())"), "f 1 g call/0 call/2");
+        assert_eq!(rpn("xs[1:] + [a, b][0]"), "xs 1 _ [:] a b list/2 0 [] +");
+        assert_eq!(rpn("m.keys"), "m keys .");
+    }
+
+    #[test]
+    fn statements_end_with_their_keyword() {
+        assert_eq!(rpn("print 1 + 2;"), "1 2 + print");
+        assert_eq!(
+            rpn("fun f(a, b) { return a; }\n{ var x = f(1, 2); }"),
+            "(a b) { a return } f fun\n{ f 1 2 call/2 x var }"
+        );
+    }
+}
@@ -1,31 +1,43 @@
-use crate::token::{Literal, Token, TokenType};
+use crate::token::{Literal, Span, Token, TokenType, Trivia, TriviaKind};
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read};
 
 lazy_static! {
-    static ref KEYWORDS: HashMap<String, TokenType> = {
+    pub static ref KEYWORDS: HashMap<String, TokenType> = {
         let mut m = HashMap::new();
         m.insert("and".to_owned(), TokenType::And);
+        m.insert("break".to_owned(), TokenType::Break);
+        m.insert("catch".to_owned(), TokenType::Catch);
         m.insert("class".to_owned(), TokenType::Class);
+        m.insert("continue".to_owned(), TokenType::Continue);
         m.insert("else".to_owned(), TokenType::Else);
         m.insert("false".to_owned(), TokenType::False);
+        m.insert("finally".to_owned(), TokenType::Finally);
         m.insert("for".to_owned(), TokenType::For);
         m.insert("fun".to_owned(), TokenType::Fun);
         m.insert("if".to_owned(), TokenType::If);
+        m.insert("in".to_owned(), TokenType::In);
         m.insert("nil".to_owned(), TokenType::Nil);
         m.insert("or".to_owned(), TokenType::Or);
         m.insert("print".to_owned(), TokenType::Print);
         m.insert("return".to_owned(), TokenType::Return);
         m.insert("super".to_owned(), TokenType::Super);
         m.insert("this".to_owned(), TokenType::This);
+        m.insert("throw".to_owned(), TokenType::Throw);
         m.insert("true".to_owned(), TokenType::True);
+        m.insert("try".to_owned(), TokenType::Try);
         m.insert("var".to_owned(), TokenType::Var);
         m.insert("while".to_owned(), TokenType::While);
         m
     };
 }
 
+/// Number of bytes requested from the underlying reader at a time.
+const READ_CHUNK_SIZE: usize = 8192;
+
 /// `ScannerError` is an enum of errors that can occur while scanning tokens.
+#[derive(Debug)]
 pub enum ScannerError {
     /// An `UnexpectedChar` error occurs if the scanner encounters a byte that
     /// it does not know how to handle
@@ -33,14 +45,30 @@ pub enum ScannerError {
     /// An `UnterminatedString` error occurs if the scanner finds an unterminated
     /// string literal.
     UnterminatedString(usize),
+    /// An `UnterminatedComment` error occurs if the scanner finds a block comment
+    /// that is never closed.
+    UnterminatedComment(usize),
+    /// An `Io` error occurs if the reader the scanner is pulling source from fails.
+    Io(usize, io::Error),
 }
 
+/// A `Scanner` turns Lox source into `Token`s on demand.
+/// Iterating over a `Scanner` yields one token at a time, ending with an `Eof` token.
+/// Source is either held in memory up front or pulled from a reader in chunks,
+/// with bytes that precede the token being scanned discarded as scanning goes.
 pub struct Scanner {
     source: Vec<u8>,
-    tokens: Vec<Token>,
+    reader: Option<Box<dyn Read>>,
+    io_error: Option<io::Error>,
+    tokens: VecDeque<Token>,
     start: usize,
     current: usize,
+    discarded: usize,
     line: usize,
+    lossless: bool,
+    pending_trivia: Vec<Trivia>,
+    seen_newline: bool,
+    finished: bool,
 }
 
 impl Scanner {
@@ -50,33 +78,114 @@ impl Scanner {
     pub fn new(source: String) -> Self {
         Scanner {
             source: source.into_bytes(),
-            tokens: vec![],
+            reader: None,
+            io_error: None,
+            tokens: VecDeque::new(),
             start: 0,
             current: 0,
+            discarded: 0,
             line: 1,
+            lossless: false,
+            pending_trivia: vec![],
+            seen_newline: false,
+            finished: false,
         }
     }
 
-    /// `scan_tokens` parses the bytes held by the `Scanner` and returns an immutable reference
-    /// to a `Vec` of `Token`s.
+    /// Create a `Scanner` that reads its source from `reader` as tokens are requested,
+    /// rather than requiring the whole program in memory first.
+    pub fn from_reader<R: Read + 'static>(reader: R) -> Self {
+        let mut scanner = Scanner::new(String::new());
+        scanner.reader = Some(Box::new(reader));
+        scanner
+    }
+
+    /// Create a `Scanner` that keeps whitespace and comments.
+    /// Every token produced will carry the trivia surrounding it, so that
+    /// concatenating `Token::to_source` for each token reproduces `source`.
+    /// Trivia on the same line after a token becomes that token's trailing
+    /// trivia; everything else is leading trivia of the next token.
+    pub fn new_lossless(source: String) -> Self {
+        let mut scanner = Scanner::new(source);
+        scanner.lossless = true;
+        scanner
+    }
+
+    /// `scan_tokens` scans all of the remaining source and returns a `Vec` of `Token`s.
     /// If an error occurs while scanning, it will be returned with the number of the line
     ///  on which the error was found.
-    pub fn scan_tokens(&mut self) -> Result<&Vec<Token>, ScannerError> {
-        while !self.at_end() {
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, ScannerError> {
+        self.collect()
+    }
+
+    /// `next_complete_token` scans until the token at the front of the queue can no
+    /// longer gain trailing trivia, and returns it.
+    fn next_complete_token(&mut self) -> Option<Result<Token, ScannerError>> {
+        loop {
+            if self.tokens.len() > 1 || (self.seen_newline && !self.tokens.is_empty()) {
+                return self.tokens.pop_front().map(Ok);
+            }
+            if self.finished {
+                return self.tokens.pop_front().map(Ok);
+            }
+            if self.at_end() {
+                if let Some(e) = self.io_error.take() {
+                    self.finished = true;
+                    return Some(Err(ScannerError::Io(self.line, e)));
+                }
+                let offset = self.discarded + self.current;
+                let span = Span::new(offset, offset, self.line);
+                let mut eof = Token::new(TokenType::Eof, String::from(""), None, span);
+                eof.set_leading_trivia(std::mem::take(&mut self.pending_trivia));
+                self.tokens.push_back(eof);
+                self.finished = true;
+                continue;
+            }
+            self.discard_scanned();
             self.start = self.current;
-            self.scan_token()?;
+            if let Err(e) = self.scan_token() {
+                self.skip_error();
+                return Some(Err(e));
+            }
         }
+    }
 
-        self.tokens.push(Token::new(
-            TokenType::Eof,
-            String::from(""),
-            None,
-            self.line,
-        ));
-        Ok(&self.tokens)
+    /// Drop source bytes that no token can refer to anymore, so streaming input is
+    /// not kept in memory in its entirety.
+    fn discard_scanned(&mut self) {
+        if self.current >= READ_CHUNK_SIZE {
+            self.source.drain(..self.current);
+            self.discarded += self.current;
+            self.current = 0;
+            self.start = 0;
+        }
+    }
+
+    /// `fill` makes sure the byte `lookahead` places past `current` has been read
+    /// from the reader, if there is one and it has that many bytes left.
+    fn fill(&mut self, lookahead: usize) {
+        while self.current + lookahead >= self.source.len() {
+            let reader = match self.reader.as_mut() {
+                Some(reader) => reader,
+                None => return,
+            };
+            let mut chunk = [0; READ_CHUNK_SIZE];
+            match reader.read(&mut chunk) {
+                Ok(0) => {
+                    self.reader = None;
+                }
+                Ok(n) => self.source.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    self.io_error = Some(e);
+                    self.reader = None;
+                }
+            }
+        }
     }
 
-    fn at_end(&self) -> bool {
+    fn at_end(&mut self) -> bool {
+        self.fill(0);
         self.current >= self.source.len()
     }
 
@@ -87,12 +196,16 @@ impl Scanner {
             b')' => self.add_token(TokenType::RightParen, None),
             b'{' => self.add_token(TokenType::LeftBrace, None),
             b'}' => self.add_token(TokenType::RightBrace, None),
+            b'[' => self.add_token(TokenType::LeftBracket, None),
+            b']' => self.add_token(TokenType::RightBracket, None),
             b',' => self.add_token(TokenType::Comma, None),
             b'.' => self.add_token(TokenType::Dot, None),
             b'-' => self.add_token(TokenType::Minus, None),
             b'+' => self.add_token(TokenType::Plus, None),
             b';' => self.add_token(TokenType::Semicolon, None),
             b'*' => self.add_token(TokenType::Star, None),
+            b'?' => self.add_token(TokenType::Question, None),
+            b':' => self.add_token(TokenType::Colon, None),
             b'!' => {
                 if self.char_match(b'=') {
                     self.add_token(TokenType::BangEqual, None);
@@ -126,6 +239,9 @@ impl Scanner {
                     while self.peek() != b'\n' && !self.at_end() {
                         self.advance();
                     }
+                    self.add_trivia(TriviaKind::LineComment);
+                } else if self.char_match(b'*') {
+                    self.block_comment()?;
                 } else {
                     self.add_token(TokenType::Slash, None)
                 }
@@ -133,8 +249,16 @@ impl Scanner {
             b'"' => {
                 self.string()?;
             }
-            b' ' | b'\r' | b'\t' => (),
-            b'\n' => self.line += 1,
+            b' ' | b'\r' | b'\t' => {
+                while matches!(self.peek(), b' ' | b'\r' | b'\t') {
+                    self.advance();
+                }
+                self.add_trivia(TriviaKind::Whitespace);
+            }
+            b'\n' => {
+                self.line += 1;
+                self.add_trivia(TriviaKind::Newline);
+            }
             other => {
                 if other.is_ascii_digit() {
                     self.number()?;
@@ -154,7 +278,7 @@ impl Scanner {
         curr_char
     }
 
-    fn peek(&self) -> u8 {
+    fn peek(&mut self) -> u8 {
         if self.at_end() {
             b'\0'
         } else {
@@ -162,7 +286,8 @@ impl Scanner {
         }
     }
 
-    fn peek_next(&self) -> u8 {
+    fn peek_next(&mut self) -> u8 {
+        self.fill(1);
         if self.current + 1 >= self.source.len() {
             b'\0'
         } else {
@@ -191,6 +316,21 @@ impl Scanner {
         Ok(())
     }
 
+    fn block_comment(&mut self) -> Result<(), ScannerError> {
+        while !(self.peek() == b'*' && self.peek_next() == b'/') {
+            if self.at_end() {
+                return Err(ScannerError::UnterminatedComment(self.line));
+            }
+            if self.advance() == b'\n' {
+                self.line += 1;
+            }
+        }
+        self.advance();
+        self.advance();
+        self.add_trivia(TriviaKind::BlockComment);
+        Ok(())
+    }
+
     fn number(&mut self) -> Result<(), ScannerError> {
         while self.peek().is_ascii_digit() {
             self.advance();
@@ -236,8 +376,44 @@ impl Scanner {
     fn add_token(&mut self, token_type: TokenType, literal: Option<Literal>) {
         let buf = &self.source[self.start..self.current];
         let text = String::from_utf8_lossy(buf);
-        self.tokens
-            .push(Token::new(token_type, text.to_string(), literal, self.line))
+        let span = Span::new(
+            self.discarded + self.start,
+            self.discarded + self.current,
+            self.line,
+        );
+        let mut token = Token::new(token_type, text.to_string(), literal, span);
+        token.set_leading_trivia(std::mem::take(&mut self.pending_trivia));
+        self.tokens.push_back(token);
+        self.seen_newline = false;
+    }
+
+    /// Record the text between `start` and `current` as trivia when scanning
+    /// losslessly. Trivia on the same line as the previous token is attached to
+    /// it; anything after a newline waits for the next token.
+    fn add_trivia(&mut self, kind: TriviaKind) {
+        if !self.lossless {
+            return;
+        }
+        let text = String::from_utf8_lossy(&self.source[self.start..self.current]).to_string();
+        let trailing = !self.seen_newline && kind != TriviaKind::Newline;
+        if text.contains('\n') {
+            self.seen_newline = true;
+        }
+        let trivia = Trivia::new(kind, text);
+        match self.tokens.back_mut() {
+            Some(token) if trailing => token.push_trailing_trivia(trivia),
+            _ => self.pending_trivia.push(trivia),
+        }
+    }
+
+    /// `skip_error` keeps the text of a failed token as trivia, so a lossless scan
+    /// still covers the whole source. A stray non-ASCII character is taken whole
+    /// rather than split partway through its encoding.
+    fn skip_error(&mut self) {
+        while self.peek() & 0xC0 == 0x80 {
+            self.advance();
+        }
+        self.add_trivia(TriviaKind::Error);
     }
 
     fn char_match(&mut self, expected: u8) -> bool {
@@ -260,3 +436,117 @@ impl Scanner {
         Ok(str_value.unwrap())
     }
 }
+
+impl Iterator for Scanner {
+    type Item = Result<Token, ScannerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_complete_token()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sources that a lossless scan must reproduce exactly, including ones that
+    /// fail to scan.
+    const CORPUS: &[&str] = &[
+        "",
+        "print 1 + 2;",
+        "  var x = 1; // a comment\n\n/* a block\n comment */ print x;\n",
+        "var a = 1;\r\nvar b = 2; // trailing\r\n\r\nprint a + b;\r\n",
+        "var s = \"a string\nspread over\r\nlines\";\nprint s;",
+        "print \"héllo wörld ✓\"; // ünïcödé comment\n/* 日本語 */",
+        "var x = 1 @ 2;\nprint x;",
+        "print é;\nprint 1;",
+        "print 1 # 2 λ 3;",
+        "print \"unterminated\n;",
+        "print 1; /* unterminated\ncomment",
+        "\n\n  \t\n",
+    ];
+
+    /// `Chunks` reads its source a few bytes at a time, so tokens, comments and
+    /// multi-byte characters straddle the reads.
+    struct Chunks {
+        source: Vec<u8>,
+        position: usize,
+        size: usize,
+    }
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let end = (self.position + self.size)
+                .min(self.source.len())
+                .min(self.position + buf.len());
+            let n = end - self.position;
+            buf[..n].copy_from_slice(&self.source[self.position..end]);
+            self.position = end;
+            Ok(n)
+        }
+    }
+
+    /// `round_trip` concatenates the source of every token `scanner` produces,
+    /// skipping the errors it reports along the way.
+    fn round_trip(scanner: Scanner) -> String {
+        scanner
+            .filter_map(Result::ok)
+            .map(|token| token.to_source())
+            .collect()
+    }
+
+    #[test]
+    fn lossless_scan_reproduces_source() {
+        for source in CORPUS {
+            let scanner = Scanner::new_lossless(source.to_string());
+            assert_eq!(round_trip(scanner), *source);
+        }
+    }
+
+    #[test]
+    fn lossless_scan_from_reader_reproduces_source() {
+        for source in CORPUS {
+            for size in 1..=4 {
+                let mut scanner = Scanner::from_reader(Chunks {
+                    source: source.as_bytes().to_vec(),
+                    position: 0,
+                    size,
+                });
+                scanner.lossless = true;
+                assert_eq!(
+                    round_trip(scanner),
+                    *source,
+                    "read {} bytes at a time",
+                    size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lossless_scan_from_reader_reproduces_long_source() {
+        // Long enough that the scanner discards source it has finished with.
+        let source = CORPUS.join("\n").repeat(READ_CHUNK_SIZE / 100);
+        assert!(source.len() > 2 * READ_CHUNK_SIZE);
+        let mut scanner = Scanner::from_reader(Chunks {
+            source: source.as_bytes().to_vec(),
+            position: 0,
+            size: 1000,
+        });
+        scanner.lossless = true;
+        assert_eq!(round_trip(scanner), source);
+    }
+
+    #[test]
+    fn lossless_scan_reports_errors() {
+        let scanner = Scanner::new_lossless(String::from("print 1 @ 2;\nprint \"open"));
+        let errors: Vec<_> = scanner.filter_map(Result::err).collect();
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                ScannerError::UnexpectedChar(1),
+                ScannerError::UnterminatedString(2)
+            ]
+        ));
+    }
+}
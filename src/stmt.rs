@@ -0,0 +1,4 @@
+//! Statement nodes and their visitors, generated by `build.rs` from the
+//! statement definitions there.
+
+include!(concat!(env!("OUT_DIR"), "/stmt.rs"));
@@ -0,0 +1,326 @@
+//! Helpers shared by the unit tests: parsing source that is known to be valid,
+//! a small deterministic random number generator, generators of random Lox
+//! expressions built on it, and a fold that takes the groupings out of a tree.
+
+use crate::ast::{Ast, ExprId};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::visit::Fold;
+
+/// `parse` parses `source`, which must be free of errors.
+pub fn parse(source: &str) -> Ast {
+    match Parser::from_scanner(Scanner::new(source.to_owned())).parse() {
+        Ok(ast) => ast,
+        Err(errors) => panic!("parsing {}: {:?}", source, errors),
+    }
+}
+
+/// `Rng` is a xorshift generator. Tests seed it with fixed values, so a failure
+/// always reproduces.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // A zero state would only ever produce zeroes.
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// `below` picks a number from `0` up to but not including `n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+/// The grammar levels of the expressions `expression` generates, loosest first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Comma,
+    Assignment,
+    Conditional,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Level {
+    /// The binary operators at this level, if it is a level of binary operators.
+    fn operators(self) -> &'static [&'static str] {
+        match self {
+            Level::Comma => &[","],
+            Level::Or => &["or"],
+            Level::And => &["and"],
+            Level::Equality => &["==", "!="],
+            Level::Comparison => &["<", "<=", ">", ">="],
+            Level::Term => &["+", "-"],
+            Level::Factor => &["*", "/"],
+            _ => &[],
+        }
+    }
+}
+
+/// `expression` generates the tokens of a random expression using grouping,
+/// literals and the unary, binary, logical, conditional and comma operators, nested at
+/// most `depth` deep. This is the grammar the recursive-descent parser knows.
+pub fn expression(rng: &mut Rng, depth: usize) -> Vec<String> {
+    let mut generator = Generator {
+        rng,
+        every: false,
+        tokens: vec![],
+    };
+    generator.at_level(Level::Comma, depth);
+    generator.tokens
+}
+
+/// `any_expression` generates the tokens of a random expression that may use
+/// anything in the expression grammar: besides what `expression` uses, variables,
+/// assignment, calls, properties, indexing and slicing, and list, map and lambda
+/// literals.
+pub fn any_expression(rng: &mut Rng, depth: usize) -> Vec<String> {
+    let mut generator = Generator {
+        rng,
+        every: true,
+        tokens: vec![],
+    };
+    generator.at_level(Level::Comma, depth);
+    generator.tokens
+}
+
+struct Generator<'a> {
+    rng: &'a mut Rng,
+    /// Whether to use the whole grammar, rather than what `expression` uses.
+    every: bool,
+    tokens: Vec<String>,
+}
+
+impl Generator<'_> {
+    fn push(&mut self, token: &str) {
+        self.tokens.push(token.to_owned());
+    }
+
+    /// `next` is the level below `level` in the grammar being generated.
+    fn next(&self, level: Level) -> Level {
+        match level {
+            Level::Comma if self.every => Level::Assignment,
+            Level::Comma | Level::Assignment => Level::Conditional,
+            Level::Conditional => Level::Or,
+            Level::Or => Level::And,
+            Level::And => Level::Equality,
+            Level::Equality => Level::Comparison,
+            Level::Comparison => Level::Term,
+            Level::Term => Level::Factor,
+            Level::Factor => Level::Unary,
+            Level::Unary if self.every => Level::Call,
+            Level::Unary | Level::Call | Level::Primary => Level::Primary,
+        }
+    }
+
+    fn at_level(&mut self, level: Level, depth: usize) {
+        // Most of the time an operand is just the next level down, so that
+        // expressions stay small but every level still turns up.
+        if depth == 0 || level == Level::Primary {
+            return self.primary(depth);
+        }
+        if self.rng.below(3) > 0 {
+            return self.at_level(self.next(level), depth);
+        }
+        match level {
+            Level::Assignment => {
+                self.target(depth - 1);
+                self.push("=");
+                self.at_level(Level::Assignment, depth - 1);
+            }
+            Level::Conditional => {
+                self.at_level(Level::Or, depth - 1);
+                self.push("?");
+                self.at_level(Level::Comma, depth - 1);
+                self.push(":");
+                self.at_level(Level::Conditional, depth - 1);
+            }
+            Level::Unary => {
+                let operator = *self.rng.pick(&["!", "-"]);
+                self.push(operator);
+                self.at_level(Level::Unary, depth - 1);
+            }
+            Level::Call => {
+                self.at_level(Level::Primary, depth - 1);
+                for _ in 0..=self.rng.below(2) {
+                    self.postfix(depth - 1);
+                }
+            }
+            _ => {
+                self.at_level(self.next(level), depth - 1);
+                for _ in 0..=self.rng.below(2) {
+                    let operator = *self.rng.pick(level.operators());
+                    self.push(operator);
+                    self.at_level(self.next(level), depth - 1);
+                }
+            }
+        }
+    }
+
+    /// `target` generates something that can be assigned to: a variable, or an
+    /// index into a list or map.
+    fn target(&mut self, depth: usize) {
+        if depth == 0 || self.rng.below(2) == 0 {
+            return self.variable();
+        }
+        self.at_level(Level::Call, depth - 1);
+        self.push("[");
+        self.at_level(Level::Comma, depth - 1);
+        self.push("]");
+    }
+
+    /// `postfix` generates a call, a property, an index or a slice of whatever
+    /// comes before it.
+    fn postfix(&mut self, depth: usize) {
+        match self.rng.below(4) {
+            0 => {
+                self.push("(");
+                self.separated(depth, ")");
+            }
+            1 => {
+                self.push(".");
+                let name = *self.rng.pick(&["len", "x"]);
+                self.push(name);
+            }
+            2 => {
+                self.push("[");
+                self.at_level(Level::Comma, depth);
+                self.push("]");
+            }
+            _ => {
+                self.push("[");
+                if self.rng.below(2) == 0 {
+                    self.at_level(Level::Comma, depth);
+                }
+                self.push(":");
+                if self.rng.below(2) == 0 {
+                    self.at_level(Level::Comma, depth);
+                }
+                self.push("]");
+            }
+        }
+    }
+
+    /// `separated` generates up to two comma-separated expressions, such as call
+    /// arguments, followed by `closing`.
+    fn separated(&mut self, depth: usize, closing: &str) {
+        for i in 0..self.rng.below(3) {
+            if i > 0 {
+                self.push(",");
+            }
+            self.at_level(Level::Assignment, depth);
+        }
+        self.push(closing);
+    }
+
+    fn variable(&mut self) {
+        let name = *self.rng.pick(&["a", "b", "c"]);
+        self.push(name);
+    }
+
+    fn primary(&mut self, depth: usize) {
+        if depth > 0 && self.rng.below(4) == 0 {
+            self.push("(");
+            self.at_level(Level::Comma, depth - 1);
+            self.push(")");
+            return;
+        }
+        if self.every && depth > 0 && self.rng.below(3) == 0 {
+            return self.collection_or_lambda(depth - 1);
+        }
+        let literal = match self.rng.below(if self.every { 7 } else { 6 }) {
+            0 => String::from("nil"),
+            1 => String::from("true"),
+            2 => String::from("false"),
+            3 => format!("\"s{}\"", self.rng.below(10)),
+            4 => format!("{}.5", self.rng.below(10)),
+            5 => self.rng.below(100).to_string(),
+            _ => return self.variable(),
+        };
+        self.tokens.push(literal);
+    }
+
+    /// `collection_or_lambda` generates a list, map or lambda.
+    fn collection_or_lambda(&mut self, depth: usize) {
+        match self.rng.below(3) {
+            0 => {
+                self.push("[");
+                self.separated(depth, "]");
+            }
+            1 => {
+                self.push("{");
+                for i in 0..self.rng.below(3) {
+                    if i > 0 {
+                        self.push(",");
+                    }
+                    self.at_level(Level::Assignment, depth);
+                    self.push(":");
+                    self.at_level(Level::Assignment, depth);
+                }
+                self.push("}");
+            }
+            _ => {
+                self.push("fun");
+                self.push("(");
+                for i in 0..self.rng.below(3) {
+                    if i > 0 {
+                        self.push(",");
+                    }
+                    self.variable();
+                }
+                self.push(")");
+                self.push("{");
+                for _ in 0..self.rng.below(3) {
+                    let keyword = *self.rng.pick(&["return", "print", ""]);
+                    if !keyword.is_empty() {
+                        self.push(keyword);
+                    }
+                    let start = self.tokens.len();
+                    self.at_level(Level::Comma, depth);
+                    // At the start of a statement, an empty map reads as a block.
+                    let block = [String::from("{"), String::from("}")];
+                    if keyword.is_empty() && self.tokens[start..].starts_with(&block) {
+                        self.tokens.insert(start, String::from("("));
+                        self.push(")");
+                    }
+                    self.push(";");
+                }
+                self.push("}");
+            }
+        }
+    }
+}
+
+/// `StripGroupings` folds a tree into the same tree without its `Grouping` nodes,
+/// which leaves a printer to work out where parentheses are needed.
+pub struct StripGroupings;
+
+impl Fold for StripGroupings {
+    fn fold_grouping_expr(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        expression: ExprId,
+        out: &mut Ast,
+    ) -> ExprId {
+        self.fold_expr(ast, expression, out)
+    }
+}
@@ -1,9 +1,14 @@
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TokenType {
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -11,6 +16,8 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Question,
+    Colon,
 
     Bang,
     BangEqual,
@@ -26,26 +33,34 @@ pub enum TokenType {
     Number,
 
     And,
+    Break,
+    Catch,
     Class,
+    Continue,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
+    In,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
     Eof,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
 pub enum Literal {
     LoxString(String),
     Number(f64),
@@ -53,12 +68,106 @@ pub enum Literal {
     Nil,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literal::LoxString(value) => write!(f, "{}", value),
+            Literal::Number(value) => write!(f, "{}", value),
+            Literal::Boolean(value) => write!(f, "{}", value),
+            Literal::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+/// A `Span` is a range of source text, given as byte offsets from the start of
+/// the source, along with the line the range belongs to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    start: usize,
+    end: usize,
+    line: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize) -> Self {
+        Span { start, end, line }
+    }
+
+    /// Offset of the first byte in the span.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Offset one past the last byte in the span.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// `to` returns a span covering everything from the start of `self` to the end of `other`.
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(self.start, other.end, self.line)
+    }
+}
+
+/// The kinds of source text that the scanner skips over between tokens.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TriviaKind {
+    /// A run of spaces, tabs and carriage returns.
+    Whitespace,
+    /// A single `\n`.
+    Newline,
+    /// A `//` comment, not including the terminating newline.
+    LineComment,
+    /// A `/* ... */` comment.
+    BlockComment,
+    /// Text that failed to scan, such as an unexpected character or an
+    /// unterminated string or comment. The scanner reports an error for it too.
+    Error,
+}
+
+/// A piece of `Trivia` is source text that carries no meaning for the parser
+/// but has to be kept around to reproduce the source exactly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trivia {
+    kind: TriviaKind,
+    text: String,
+}
+
+impl Trivia {
+    pub fn new(kind: TriviaKind, text: String) -> Self {
+        Trivia { kind, text }
+    }
+
+    pub fn kind(&self) -> TriviaKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Token {
+    #[serde(rename = "type")]
     token_type: TokenType,
     lexeme: String,
     literal: Option<Literal>,
-    line: usize,
+    span: Span,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trivia: Option<Box<TokenTrivia>>,
+}
+
+/// Trivia is only collected by a lossless scan, so it lives behind a `Box`
+/// to keep ordinary tokens small.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+struct TokenTrivia {
+    leading: Vec<Trivia>,
+    trailing: Vec<Trivia>,
 }
 
 impl Token {
@@ -66,13 +175,14 @@ impl Token {
         token_type: TokenType,
         lexeme: String,
         literal: Option<Literal>,
-        line: usize,
+        span: Span,
     ) -> Self {
         Token {
             token_type,
             lexeme,
             literal,
-            line,
+            span,
+            trivia: None,
         }
     }
 
@@ -88,10 +198,65 @@ impl Token {
     }
 
     pub fn line(&self) -> usize {
-        self.line
+        self.span.line()
+    }
+
+    /// The location of the token's lexeme, not including any trivia.
+    pub fn span(&self) -> Span {
+        self.span
     }
 
     pub fn lexeme(&self) -> String {
         self.lexeme.clone()
     }
+
+    /// Trivia found between the previous token's trailing trivia and this token.
+    pub fn leading_trivia(&self) -> &[Trivia] {
+        match &self.trivia {
+            Some(trivia) => &trivia.leading,
+            None => &[],
+        }
+    }
+
+    /// Trivia following this token on the same line.
+    pub fn trailing_trivia(&self) -> &[Trivia] {
+        match &self.trivia {
+            Some(trivia) => &trivia.trailing,
+            None => &[],
+        }
+    }
+
+    pub fn set_leading_trivia(&mut self, trivia: Vec<Trivia>) {
+        if trivia.is_empty() && self.trivia.is_none() {
+            return;
+        }
+        self.trivia.get_or_insert_with(Default::default).leading = trivia;
+    }
+
+    pub fn push_trailing_trivia(&mut self, trivia: Trivia) {
+        self.trivia
+            .get_or_insert_with(Default::default)
+            .trailing
+            .push(trivia);
+    }
+
+    /// `to_source` returns the exact source text this token was scanned from,
+    /// including its leading and trailing trivia.
+    pub fn to_source(&self) -> String {
+        let mut source = String::new();
+        for trivia in self.leading_trivia() {
+            source.push_str(trivia.text());
+        }
+        source.push_str(&self.lexeme);
+        for trivia in self.trailing_trivia() {
+            source.push_str(trivia.text());
+        }
+        source
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.lexeme)
+    }
 }
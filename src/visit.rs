@@ -0,0 +1,142 @@
+//! In-place and folding passes over every kind of AST node, generated by
+//! `build.rs` from the node definitions there.
+
+include!(concat!(env!("OUT_DIR"), "/visit.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+    use crate::loxprinter::LoxPrinter;
+    use crate::testing::{self, StripGroupings};
+    use crate::token::TokenType;
+
+    const PROGRAM: &str = include_str!("../tests/fixtures/program.lox");
+
+    struct Identity;
+
+    impl Fold for Identity {}
+
+    /// `Doubler` doubles every number literal in place.
+    struct Doubler;
+
+    impl VisitorMut for Doubler {
+        fn visit_literal_expr_mut(&mut self, ast: &mut Ast, id: ExprId) {
+            if let Expr::Literal(Literal::Number(value)) = ast.get_mut(id) {
+                *value *= 2.0;
+            }
+        }
+    }
+
+    /// `ConstantFolder` replaces arithmetic on number literals with its result,
+    /// along with any grouping left around a single literal.
+    struct ConstantFolder;
+
+    impl ConstantFolder {
+        fn number(ast: &Ast, id: ExprId) -> Option<f64> {
+            match ast.get(id) {
+                Expr::Literal(Literal::Number(value)) => Some(*value),
+                _ => None,
+            }
+        }
+    }
+
+    impl Fold for ConstantFolder {
+        fn fold_binary_expr(
+            &mut self,
+            ast: &Ast,
+            id: ExprId,
+            left: ExprId,
+            operator: &Token,
+            right: ExprId,
+            out: &mut Ast,
+        ) -> ExprId {
+            let left = self.fold_expr(ast, left, out);
+            let right = self.fold_expr(ast, right, out);
+            let folded = match (Self::number(out, left), Self::number(out, right)) {
+                (Some(left), Some(right)) => match operator.token_type() {
+                    TokenType::Plus => Some(left + right),
+                    TokenType::Minus => Some(left - right),
+                    TokenType::Star => Some(left * right),
+                    TokenType::Slash => Some(left / right),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let node = match folded {
+                Some(value) => Expr::Literal(Literal::Number(value)),
+                None => Expr::Binary(left, operator.clone(), right),
+            };
+            out.add(node, ast.span(id))
+        }
+
+        fn fold_grouping_expr(
+            &mut self,
+            ast: &Ast,
+            id: ExprId,
+            expression: ExprId,
+            out: &mut Ast,
+        ) -> ExprId {
+            let expression = self.fold_expr(ast, expression, out);
+            match Self::number(out, expression) {
+                Some(_) => expression,
+                None => out.add(Expr::Grouping(expression), ast.span(id)),
+            }
+        }
+    }
+
+    #[test]
+    fn identity_fold_copies_the_tree() {
+        for source in [PROGRAM, "(1 + 2) * -x, y ? z : w", ""] {
+            let ast = testing::parse(source);
+            let folded = Identity.fold(&ast);
+            // Spans are kept along with the shape of the tree.
+            assert_eq!(json::ast_to_json(&folded), json::ast_to_json(&ast));
+        }
+    }
+
+    #[test]
+    fn fold_can_drop_nodes() {
+        let ast = testing::parse("print ((1 + (2)) * ((x)));\nfun f() { return (\"s\"); }");
+        let folded = StripGroupings.fold(&ast);
+        assert_eq!(
+            LoxPrinter::new().print(&folded),
+            "print (1 + 2) * x;\nfun f() {\n    return \"s\";\n}"
+        );
+    }
+
+    #[test]
+    fn fold_can_replace_nodes() {
+        let ast = testing::parse("var a = (1 + 2) * 4 - x;\nprint [2 / 4, \"s\" + \"t\"];");
+        let folded = ConstantFolder.fold(&ast);
+        assert_eq!(
+            LoxPrinter::new().print(&folded),
+            "var a = 12 - x;\nprint [0.5, \"s\" + \"t\"];"
+        );
+    }
+
+    #[test]
+    fn visitor_mut_rewrites_nodes_in_place() {
+        let mut ast = testing::parse(
+            "var a = 1;\n\
+             fun f(n) { while (n < 3) n = n + [4][0]; return fun () { return 5; }; }\n\
+             try { throw 6; } catch (e) { print {7: -8}; }",
+        );
+        let nodes = ast.len();
+        Doubler.visit_ast_mut(&mut ast);
+        assert_eq!(ast.len(), nodes);
+        assert_eq!(
+            LoxPrinter::new().print(&ast),
+            "var a = 2;\n\
+             fun f(n) {\n    \
+             while (n < 6) n = n + [8][0];\n    \
+             return fun () {\n        return 10;\n    };\n\
+             }\n\
+             try {\n    throw 12;\n} catch (e) {\n    print {14: -16};\n}"
+        );
+
+        let mut ast = testing::parse("1 + (2, 3)");
+        Doubler.visit_ast_mut(&mut ast);
+        assert_eq!(LoxPrinter::new().print(&ast), "2 + (4, 6)");
+    }
+}
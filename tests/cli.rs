@@ -0,0 +1,216 @@
+//! Runs the `rox` binary to check how its command line is read.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+const PROGRAM: &str = "tests/fixtures/program.lox";
+const PROGRAM_JSON: &str = include_str!("fixtures/program.json");
+
+fn rox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rox"))
+        .args(args)
+        .output()
+        .expect("rox runs")
+}
+
+/// `rox_with_input` runs `rox` with `input` piped to its stdin.
+fn rox_with_input(args: &[&str], input: &str) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rox"));
+    command.args(args);
+    piped(command, input)
+}
+
+/// `repl` feeds `input` to `rox repl`, with a home directory of its own under
+/// `name` to keep its history in.
+fn repl(name: &str, input: &str) -> Output {
+    let home = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::create_dir_all(&home).expect("the home directory is created");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rox"));
+    command.arg("repl").env("HOME", &home);
+    piped(command, input)
+}
+
+fn piped(mut command: Command, input: &str) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("rox runs");
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input.as_bytes())
+        .expect("rox reads its input");
+    child.wait_with_output().expect("rox finishes")
+}
+
+/// `assert_rejected` checks that `args` are refused before any script runs.
+fn assert_rejected(args: &[&str]) {
+    let output = rox(args);
+    assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    assert!(output.stdout.is_empty(), "{:?}", args);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--emit can only be given with"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn emit_is_rejected_with_a_script() {
+    assert_rejected(&["--emit=rpn", PROGRAM]);
+    assert_rejected(&["--emit=ast-json", PROGRAM]);
+}
+
+#[test]
+fn emit_is_rejected_with_run() {
+    assert_rejected(&["--emit=tokens-json", "run", PROGRAM]);
+    assert_rejected(&["run", "--emit=dot", PROGRAM]);
+}
+
+#[test]
+fn emit_is_rejected_with_eval() {
+    assert_rejected(&["--emit=sexpr", "-e", "print 1;"]);
+}
+
+#[test]
+fn emit_is_rejected_with_check() {
+    assert_rejected(&["check", "--emit=rpn", PROGRAM]);
+}
+
+#[test]
+fn emit_is_rejected_with_tokens_unless_it_names_tokens() {
+    assert_rejected(&["tokens", "--emit=dot", PROGRAM]);
+    assert_rejected(&["--emit=ast-json", "tokens", PROGRAM]);
+    assert_rejected(&["tokens", "--json", "--emit=sexpr", PROGRAM]);
+}
+
+#[test]
+fn emit_formats_parse() {
+    let output = rox(&["--emit=ast-json", "parse", PROGRAM]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), PROGRAM_JSON);
+
+    let output = rox(&["parse", "--emit=rpn", "-"]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn tokens_json_prints_tokens() {
+    let emitted = rox(&["--emit=tokens-json", "parse", PROGRAM]);
+    let flagged = rox(&["tokens", "--json", PROGRAM]);
+    assert!(emitted.status.success());
+    assert!(String::from_utf8_lossy(&emitted.stdout).starts_with('['));
+    assert_eq!(emitted.stdout, flagged.stdout);
+}
+
+#[test]
+fn eval_runs_its_source() {
+    let output = rox(&["-e", "print 1 + 2;"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+}
+
+#[test]
+fn dash_reads_the_program_from_stdin() {
+    // The program is read whole, so a statement may span lines.
+    let program = "var a = 1 +\n2;\nprint a;\n";
+    for args in [&["-"][..], &["run", "-"]] {
+        let output = rox_with_input(args, program);
+        assert!(output.status.success(), "{:?}", args);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n", "{:?}", args);
+    }
+}
+
+#[test]
+fn piped_stdin_is_run_as_a_program() {
+    let output = rox_with_input(&[], "var a = 1 +\n2;\nprint a;\n");
+    assert!(output.status.success());
+    // Without a terminal there is no prompt, and the program runs once it is read.
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+}
+
+#[test]
+fn exit_codes_tell_failures_apart() {
+    assert_eq!(rox(&["tests/fixtures/missing.lox"]).status.code(), Some(64));
+    assert_eq!(
+        rox(&["run", "tests/fixtures/missing.lox"]).status.code(),
+        Some(64)
+    );
+    assert_eq!(rox(&["-e", "print 1 +;"]).status.code(), Some(65));
+    assert_eq!(rox_with_input(&["-"], "print 1 +;").status.code(), Some(65));
+    assert_eq!(rox(&["-e", "print nil + 1;"]).status.code(), Some(70));
+    assert_eq!(
+        rox_with_input(&[], "print nil + 1;").status.code(),
+        Some(70)
+    );
+}
+
+#[test]
+fn repl_prints_tokens_and_asts() {
+    let output = repl("tokens", ":tokens 1 + 2\n:ast 1 + 2\n");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "   1 Number        1\n   \
+         1 Plus          +\n   \
+         1 Number        2\n   \
+         1 Eof           \n\
+         (+ 1 2)\n"
+    );
+}
+
+#[test]
+fn repl_lists_globals_until_reset() {
+    let output = repl("env", "var a = 1;\n:env\n:reset\n:env\n");
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "a = 1\nrange = <native fn range>\nrange = <native fn range>\n"
+    );
+}
+
+#[test]
+fn repl_loads_a_script_into_the_session() {
+    let load = format!(":load {}\ngreeting\n", PROGRAM);
+    let output = repl("load", &load);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.ends_with("<fn count>\nhello\n"), "{}", stdout);
+
+    let output = repl(
+        "load-missing",
+        ":load tests/fixtures/missing.lox\nprint 1;\n",
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("tests/fixtures/missing.lox: "),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn repl_stops_at_quit() {
+    let output = repl("quit", "print 1;\n:quit\nprint 2;\n");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+}
+
+#[test]
+fn repl_reports_unknown_commands_and_goes_on() {
+    let output = repl("unknown", ":frob\n1 + 1\n");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Unknown command ':frob'. Commands are :tokens, :ast, :env, :load, :reset and :quit.\n"
+    );
+}